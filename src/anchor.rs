@@ -0,0 +1,232 @@
+// named markers, e.g. breakpoints, bookmarks or review comments, put on positions of a document
+// anchors move along with the edits of the document, and they are stored and synced with it
+// through the server, but they are not a part of the text
+
+use super::Operation as OperationTrait;
+use super::charwise;
+use super::linewise;
+use super::selection::Bias;
+use super::selection::charwise::Selection as CharwiseSelection;
+use super::selection::linewise::{Position, Selection as LinewiseSelection};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+// documents anchors can be put on
+pub trait Document: OperationTrait {
+    type Position: Clone + Debug + Eq + Ord;
+
+    // move a position on the source of op to the target
+    fn transform_position(position: &mut Self::Position, bias: Bias, op: &Self);
+}
+
+impl Document for charwise::Operation {
+    type Position = usize;
+
+    fn transform_position(position: &mut usize, bias: Bias, op: &Self) {
+        CharwiseSelection::transform_index(position, bias, op)
+    }
+}
+
+impl Document for linewise::Operation {
+    type Position = Position;
+
+    fn transform_position(position: &mut Position, bias: Bias, op: &Self) {
+        LinewiseSelection::transform_index(position, bias, op)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnchorId(pub String);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Anchor<P> {
+    pub position: P,
+    // which side the anchor sticks to when text is inserted at the position
+    pub bias: Bias,
+}
+
+impl<P> Anchor<P> {
+    pub fn new(position: P, bias: Bias) -> Self {
+        Anchor { position, bias }
+    }
+
+    pub fn transform<O: Document<Position = P>>(mut self, op: &O) -> Self {
+        O::transform_position(&mut self.position, self.bias, op);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O::Target: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O::Target: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Target<O: Document> {
+    pub base: O::Target,
+    pub anchors: BTreeMap<AnchorId, Anchor<O::Position>>,
+}
+
+// derive would require O: PartialEq instead of O::Target: PartialEq
+impl<O: Document> PartialEq for Target<O>
+where
+    O::Target: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base && self.anchors == other.anchors
+    }
+}
+
+impl<O: Document> Eq for Target<O>
+where
+    O::Target: Eq,
+{
+}
+
+impl<O: Document> Default for Target<O> {
+    fn default() -> Self {
+        Target {
+            base: O::Target::default(),
+            anchors: BTreeMap::new(),
+        }
+    }
+}
+
+// an edit of the document along with the anchors after it
+// anchors the operation does not have move along with the edit, but the moved positions may
+// depend on the order concurrent edits are applied in. make operations with Target::operate,
+// which has every anchor, so that replicas converge
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Operation<O: Document> {
+    // anchors on the target of base, None for removed ones
+    pub anchors: BTreeMap<AnchorId, Option<Anchor<O::Position>>>,
+    // anchors put or removed by the operation, which win over the other anchors in transform
+    pub changed: BTreeSet<AnchorId>,
+    pub base: O,
+}
+
+impl<O: Document> Target<O> {
+    pub fn operate(&self, base: O) -> Operation<O> {
+        let anchors = self.anchors
+            .iter()
+            .map(|(id, anchor)| (id.clone(), Some(anchor.clone().transform(&base))))
+            .collect();
+        Operation {
+            anchors: anchors,
+            changed: BTreeSet::new(),
+            base: base,
+        }
+    }
+}
+
+impl<O: Document> Operation<O> {
+    pub fn with_content(base: O) -> Self {
+        Operation {
+            anchors: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            base: base,
+        }
+    }
+
+    // put an anchor, or move it if it already exists
+    pub fn put(&mut self, id: AnchorId, anchor: Anchor<O::Position>) -> &mut Self {
+        self.changed.insert(id.clone());
+        self.anchors.insert(id, Some(anchor));
+        self
+    }
+
+    pub fn remove(&mut self, id: AnchorId) -> &mut Self {
+        self.changed.insert(id.clone());
+        self.anchors.insert(id, None);
+        self
+    }
+
+    fn transform_anchors(
+        anchors: BTreeMap<AnchorId, Option<Anchor<O::Position>>>,
+        op: &O,
+    ) -> BTreeMap<AnchorId, Option<Anchor<O::Position>>> {
+        anchors
+            .into_iter()
+            .map(|(id, anchor)| (id, anchor.map(|anchor| anchor.transform(op))))
+            .collect()
+    }
+}
+
+impl<O: Document> Default for Operation<O> {
+    fn default() -> Self {
+        Operation::with_content(O::default())
+    }
+}
+
+impl<O: Document> OperationTrait for Operation<O> {
+    type Target = Target<O>;
+
+    fn nop(target: &Self::Target) -> Self {
+        target.operate(O::nop(&target.base))
+    }
+
+    fn apply(&self, target: &Self::Target) -> Self::Target {
+        let base = self.base.apply(&target.base);
+        let mut anchors: BTreeMap<_, _> = target
+            .anchors
+            .iter()
+            .map(|(id, anchor)| (id.clone(), anchor.clone().transform(&self.base)))
+            .collect();
+        for (id, anchor) in self.anchors.iter() {
+            match *anchor {
+                Some(ref anchor) => {
+                    anchors.insert(id.clone(), anchor.clone());
+                }
+                None => {
+                    anchors.remove(id);
+                }
+            }
+        }
+
+        Target { base, anchors }
+    }
+
+    fn compose(self, other: Self) -> Self {
+        let mut anchors = Self::transform_anchors(self.anchors, &other.base);
+        anchors.extend(other.anchors);
+        let mut changed = self.changed;
+        changed.extend(other.changed);
+        Operation {
+            anchors: anchors,
+            changed: changed,
+            base: self.base.compose(other.base),
+        }
+    }
+
+    // both operations get the same anchors, so that the results do not depend on the order
+    // an anchor changed by one of them takes the changed one, and when both change the same
+    // anchor or neither does, tie break by adopting self's.
+    // each result carries all the merged anchors but only the changed set of its own
+    // operation, so that an operation which is transformed many times, e.g. the buffer of an
+    // offline client, does not win later tie breaks with the changes of every other operation
+    fn transform(self, other: Self) -> (Self, Self) {
+        let (lhs, rhs) = self.base.transform(other.base);
+        let lhs_anchors = Self::transform_anchors(self.anchors, &rhs);
+        let mut anchors = Self::transform_anchors(other.anchors, &lhs);
+        for (id, anchor) in lhs_anchors {
+            if self.changed.contains(&id) || !other.changed.contains(&id) {
+                anchors.insert(id, anchor);
+            }
+        }
+
+        (
+            Operation {
+                anchors: anchors.clone(),
+                changed: self.changed,
+                base: lhs,
+            },
+            Operation {
+                anchors: anchors,
+                changed: other.changed,
+                base: rhs,
+            },
+        )
+    }
+}
@@ -102,6 +102,34 @@ impl Default for Operation {
 impl super::Operation for Operation {
     type Target = String;
 
+    fn checksum(target: &Self::Target) -> Option<u64> {
+        Some(super::hash_content(target))
+    }
+
+    fn invert(&self, source: &Self::Target) -> Option<Self> {
+        let mut source: &str = source;
+        let mut ret = Operation::new();
+
+        for op in self.operations.iter() {
+            use self::PrimitiveOperation::*;
+            match *op {
+                Retain(len) => {
+                    ret.retain(len);
+                    source = &source[len..];
+                }
+                Insert(ref s) => {
+                    ret.delete(s.len());
+                }
+                Delete(len) => {
+                    ret.insert(source[0..len].into());
+                    source = &source[len..];
+                }
+            }
+        }
+
+        Some(ret)
+    }
+
     fn nop(target: &Self::Target) -> Self {
         let mut ret = Operation::new();
         ret.retain(target.len());
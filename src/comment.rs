@@ -0,0 +1,371 @@
+// comment threads on ranges of a document
+// a range follows the edits of the document and shrinks when a part of its text is deleted.
+// when the whole text is deleted, the thread is kept but flagged as orphaned. a thread on an
+// empty range, e.g. a note at a position, stays there instead
+
+use super::Operation as OperationTrait;
+use super::anchor::Document;
+use super::selection::Bias;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ThreadId(pub String);
+
+// comments in a thread are ordered by their ids
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommentId(pub String);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Range<P> {
+    pub start: P,
+    pub end: P,
+    // the commented text was deleted entirely
+    pub orphaned: bool,
+}
+
+impl<P: Clone + Ord> Range<P> {
+    // a reversed range is turned around
+    pub fn new(start: P, end: P) -> Self {
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+        Range {
+            start: start,
+            end: end,
+            orphaned: false,
+        }
+    }
+
+    // text inserted at either end is not a part of the range
+    pub fn transform<O: Document<Position = P>>(mut self, op: &O) -> Self {
+        if self.start == self.end {
+            // text inserted at an empty range goes after it
+            O::transform_position(&mut self.start, Bias::Left, op);
+            self.end = self.start.clone();
+            return self;
+        }
+
+        O::transform_position(&mut self.start, Bias::Right, op);
+        O::transform_position(&mut self.end, Bias::Left, op);
+        if self.start >= self.end {
+            // an orphaned range sticks to where the text was
+            self.start = self.end.clone();
+            self.orphaned = true;
+        }
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Thread<P> {
+    pub range: Range<P>,
+    pub resolved: bool,
+    pub comments: BTreeMap<CommentId, Comment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O::Target: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O::Target: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Target<O: Document> {
+    pub base: O::Target,
+    pub threads: BTreeMap<ThreadId, Thread<O::Position>>,
+}
+
+// derive would require O: PartialEq instead of O::Target: PartialEq
+impl<O: Document> PartialEq for Target<O>
+where
+    O::Target: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base && self.threads == other.threads
+    }
+}
+
+impl<O: Document> Eq for Target<O>
+where
+    O::Target: Eq,
+{
+}
+
+impl<O: Document> Default for Target<O> {
+    fn default() -> Self {
+        Target {
+            base: O::Target::default(),
+            threads: BTreeMap::new(),
+        }
+    }
+}
+
+impl<O: Document> Target<O> {
+    pub fn operate(&self, base: O) -> Operation<O> {
+        let ranges = self.threads
+            .iter()
+            .map(|(id, thread)| (id.clone(), thread.range.clone().transform(&base)))
+            .collect();
+        Operation {
+            ranges: ranges,
+            changed: BTreeSet::new(),
+            deleted: BTreeSet::new(),
+            resolved: BTreeMap::new(),
+            comments: BTreeMap::new(),
+            base: base,
+        }
+    }
+
+    pub fn orphaned(&self) -> Vec<&ThreadId> {
+        self.threads
+            .iter()
+            .filter(|&(_, thread)| thread.range.orphaned)
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+// an edit of the document along with the changes of threads
+// like anchor::Operation, the ranges of threads are those after the edit so that replicas
+// converge, thus make operations with Target::operate
+// resolving threads and comments on threads which do not exist are ignored.
+// deleting a thread wins over concurrent changes of it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Operation<O: Document> {
+    // ranges on the target of base
+    pub ranges: BTreeMap<ThreadId, Range<O::Position>>,
+    // threads opened or moved by the operation, which win over the other ranges in transform
+    pub changed: BTreeSet<ThreadId>,
+    // threads deleted by the operation, before opening the ones in ranges
+    #[serde(default)]
+    pub deleted: BTreeSet<ThreadId>,
+    pub resolved: BTreeMap<ThreadId, bool>,
+    // comments written (Some) or deleted (None)
+    pub comments: BTreeMap<(ThreadId, CommentId), Option<Comment>>,
+    pub base: O,
+}
+
+impl<O: Document> Operation<O> {
+    pub fn with_content(base: O) -> Self {
+        Operation {
+            ranges: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            deleted: BTreeSet::new(),
+            resolved: BTreeMap::new(),
+            comments: BTreeMap::new(),
+            base: base,
+        }
+    }
+
+    // open a thread on the range, or move the thread if it already exists
+    pub fn open(&mut self, id: ThreadId, start: O::Position, end: O::Position) -> &mut Self {
+        self.changed.insert(id.clone());
+        self.ranges.insert(id, Range::new(start, end));
+        self
+    }
+
+    // delete a thread along with its comments
+    // opening it again in the same operation makes a new thread
+    pub fn delete_thread(&mut self, id: ThreadId) -> &mut Self {
+        self.ranges.remove(&id);
+        self.changed.remove(&id);
+        self.resolved.remove(&id);
+        self.comments = ::std::mem::replace(&mut self.comments, BTreeMap::new())
+            .into_iter()
+            .filter(|&((ref thread, _), _)| *thread != id)
+            .collect();
+        self.deleted.insert(id);
+        self
+    }
+
+    pub fn resolve(&mut self, id: ThreadId, resolved: bool) -> &mut Self {
+        if !self.is_deleted(&id) {
+            self.resolved.insert(id, resolved);
+        }
+        self
+    }
+
+    // write a comment, or edit it if it already exists
+    pub fn comment(&mut self, thread: ThreadId, id: CommentId, comment: Comment) -> &mut Self {
+        if !self.is_deleted(&thread) {
+            self.comments.insert((thread, id), Some(comment));
+        }
+        self
+    }
+
+    pub fn delete_comment(&mut self, thread: ThreadId, id: CommentId) -> &mut Self {
+        if !self.is_deleted(&thread) {
+            self.comments.insert((thread, id), None);
+        }
+        self
+    }
+
+    // changes of a thread deleted and not opened again are ignored like those of missing threads,
+    // so that opening it in a later operation does not bring them back
+    fn is_deleted(&self, id: &ThreadId) -> bool {
+        self.deleted.contains(id) && !self.ranges.contains_key(id)
+    }
+
+    // drop the changes of threads the other operation deletes
+    fn without_deleted(mut self, deleted: &BTreeSet<ThreadId>) -> Self {
+        for id in deleted.iter() {
+            self.ranges.remove(id);
+            self.changed.remove(id);
+            self.resolved.remove(id);
+        }
+        self.comments = ::std::mem::replace(&mut self.comments, BTreeMap::new())
+            .into_iter()
+            .filter(|&((ref thread, _), _)| !deleted.contains(thread))
+            .collect();
+        self
+    }
+
+    fn transform_ranges(
+        ranges: BTreeMap<ThreadId, Range<O::Position>>,
+        op: &O,
+    ) -> BTreeMap<ThreadId, Range<O::Position>> {
+        ranges
+            .into_iter()
+            .map(|(id, range)| (id, range.transform(op)))
+            .collect()
+    }
+}
+
+impl<O: Document> Default for Operation<O> {
+    fn default() -> Self {
+        Operation::with_content(O::default())
+    }
+}
+
+impl<O: Document> OperationTrait for Operation<O> {
+    type Target = Target<O>;
+
+    fn nop(target: &Self::Target) -> Self {
+        target.operate(O::nop(&target.base))
+    }
+
+    fn apply(&self, target: &Self::Target) -> Self::Target {
+        let base = self.base.apply(&target.base);
+        let mut threads: BTreeMap<_, _> = target
+            .threads
+            .iter()
+            .filter(|&(id, _)| !self.deleted.contains(id))
+            .map(|(id, thread)| {
+                let thread = Thread {
+                    range: thread.range.clone().transform(&self.base),
+                    resolved: thread.resolved,
+                    comments: thread.comments.clone(),
+                };
+                (id.clone(), thread)
+            })
+            .collect();
+        for (id, range) in self.ranges.iter() {
+            threads
+                .entry(id.clone())
+                .or_insert_with(|| Thread {
+                    range: range.clone(),
+                    resolved: false,
+                    comments: BTreeMap::new(),
+                })
+                .range = range.clone();
+        }
+        for (id, resolved) in self.resolved.iter() {
+            if let Some(thread) = threads.get_mut(id) {
+                thread.resolved = *resolved;
+            }
+        }
+        for (&(ref thread, ref id), comment) in self.comments.iter() {
+            if let Some(thread) = threads.get_mut(thread) {
+                match *comment {
+                    Some(ref comment) => {
+                        thread.comments.insert(id.clone(), comment.clone());
+                    }
+                    None => {
+                        thread.comments.remove(id);
+                    }
+                }
+            }
+        }
+
+        Target { base, threads }
+    }
+
+    fn compose(self, other: Self) -> Self {
+        let this = self.without_deleted(&other.deleted);
+        let mut ranges = Self::transform_ranges(this.ranges, &other.base);
+        ranges.extend(other.ranges);
+        let mut changed = this.changed;
+        changed.extend(other.changed);
+        let mut deleted = this.deleted;
+        deleted.extend(other.deleted);
+        let mut resolved = this.resolved;
+        resolved.extend(other.resolved);
+        let mut comments = this.comments;
+        comments.extend(other.comments);
+        Operation {
+            ranges: ranges,
+            changed: changed,
+            deleted: deleted,
+            resolved: resolved,
+            comments: comments,
+            base: this.base.compose(other.base),
+        }
+    }
+
+    // both operations get the same ranges as anchor::Operation does, except for the threads
+    // deleted by either of them. the changes of a deleted thread are dropped from the other
+    // operation. when both operations change the same range, resolution or comment, tie break
+    // by adopting self's
+    fn transform(self, other: Self) -> (Self, Self) {
+        let self_deleted = self.deleted.clone();
+        let other_deleted = other.deleted.clone();
+        let this = self.without_deleted(&other_deleted);
+        let other = other.without_deleted(&self_deleted);
+        let (lhs, rhs) = this.base.transform(other.base);
+
+        let lhs_ranges = Self::transform_ranges(this.ranges, &rhs);
+        let mut ranges = Self::transform_ranges(other.ranges, &lhs);
+        for (id, range) in lhs_ranges {
+            if this.changed.contains(&id) || !other.changed.contains(&id) {
+                ranges.insert(id, range);
+            }
+        }
+
+        let lhs_resolved = this.resolved;
+        let rhs_resolved = other
+            .resolved
+            .into_iter()
+            .filter(|&(ref id, _)| !lhs_resolved.contains_key(id))
+            .collect();
+        let lhs_comments = this.comments;
+        let rhs_comments = other
+            .comments
+            .into_iter()
+            .filter(|&(ref id, _)| !lhs_comments.contains_key(id))
+            .collect();
+
+        (
+            Operation {
+                ranges: ranges.clone(),
+                changed: this.changed,
+                deleted: this.deleted,
+                resolved: lhs_resolved,
+                comments: lhs_comments,
+                base: lhs,
+            },
+            Operation {
+                ranges: ranges,
+                changed: other.changed,
+                deleted: other.deleted,
+                resolved: rhs_resolved,
+                comments: rhs_comments,
+                base: rhs,
+            },
+        )
+    }
+}
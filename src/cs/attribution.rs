@@ -0,0 +1,194 @@
+// authorship of the content of a server, like blame
+// the content is split into spans, each of which was inserted by an author at a state.
+// a span is a run of bytes in charwise and a run of lines in linewise, where a line is
+// attributed to the last one who modified it
+
+use super::*;
+use super::super::{charwise, linewise, Operation};
+use super::server::Server;
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub len: usize,
+    pub author: Option<String>,
+    // the state the span was inserted at
+    pub id: Id,
+}
+
+// append span, merging it with the last one if they have the same origin
+fn push(spans: &mut Vec<Span>, span: Span) {
+    if span.len == 0 {
+        return;
+    }
+    if let Some(last) = spans.last_mut() {
+        if last.author == span.author && last.id == span.id {
+            last.len += span.len;
+            return;
+        }
+    }
+    spans.push(span);
+}
+
+// remove len from the front of spans
+fn take(spans: &mut VecDeque<Span>, mut len: usize) -> Vec<Span> {
+    let mut taken = vec![];
+    while len > 0 {
+        let mut span = match spans.pop_front() {
+            Some(span) => span,
+            None => break,
+        };
+        if span.len > len {
+            let mut rest = span.clone();
+            rest.len -= len;
+            span.len = len;
+            spans.push_front(rest);
+        }
+        len -= span.len;
+        taken.push(span);
+    }
+    taken
+}
+
+// operations whose effects can be attributed
+pub trait Attribute: Operation {
+    // the length of the content in the unit of spans
+    fn length(target: &Self::Target) -> usize;
+
+    // the spans of the target from those of the source
+    // text inserted by the operation is attributed to author at the state id
+    fn attribute(&self, spans: &[Span], author: &Option<String>, id: &Id) -> Vec<Span>;
+}
+
+impl Attribute for charwise::Operation {
+    fn length(target: &String) -> usize {
+        target.len()
+    }
+
+    fn attribute(&self, spans: &[Span], author: &Option<String>, id: &Id) -> Vec<Span> {
+        use charwise::PrimitiveOperation::*;
+
+        let mut source: VecDeque<Span> = spans.iter().cloned().collect();
+        let mut target = vec![];
+        for op in self.operations.iter() {
+            match *op {
+                Retain(len) => {
+                    for span in take(&mut source, len) {
+                        push(&mut target, span);
+                    }
+                }
+                Insert(ref s) => {
+                    let span = Span {
+                        len: s.len(),
+                        author: author.clone(),
+                        id: id.clone(),
+                    };
+                    push(&mut target, span);
+                }
+                Delete(len) => {
+                    take(&mut source, len);
+                }
+            }
+        }
+        target
+    }
+}
+
+impl Attribute for linewise::Operation {
+    fn length(target: &Vec<String>) -> usize {
+        target.len()
+    }
+
+    fn attribute(&self, spans: &[Span], author: &Option<String>, id: &Id) -> Vec<Span> {
+        use linewise::LineOperation::*;
+
+        let line = Span {
+            len: 1,
+            author: author.clone(),
+            id: id.clone(),
+        };
+        let mut source: VecDeque<Span> = spans.iter().cloned().collect();
+        let mut target = vec![];
+        for op in self.operations.iter() {
+            match *op {
+                Retain(len) => {
+                    for span in take(&mut source, len) {
+                        push(&mut target, span);
+                    }
+                }
+                Insert(_) => {
+                    push(&mut target, line.clone());
+                }
+                Delete(len) => {
+                    take(&mut source, len);
+                }
+                Modify(_) => {
+                    take(&mut source, 1);
+                    push(&mut target, line.clone());
+                }
+            }
+        }
+        target
+    }
+}
+
+// the authorship of the content of a Server at the state id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Attribution<O> {
+    id: Id,
+    spans: Vec<Span>,
+    marker: PhantomData<O>,
+}
+
+impl<O: Attribute> Attribution<O> {
+    // the authorship of the first state of the server, whose content is attributed to the
+    // author of the state as a whole since its origin is unknown
+    pub fn new(server: &Server<O>) -> Self {
+        // the history of a server always has the first state
+        let initial = server.state_at(&Id(0)).unwrap();
+        let mut spans = vec![];
+        push(
+            &mut spans,
+            Span {
+                len: O::length(&initial.content),
+                author: initial.metadata.author.clone(),
+                id: initial.id.clone(),
+            },
+        );
+        Attribution {
+            id: initial.id.clone(),
+            spans: spans,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    // the span containing the position, in bytes for charwise and in lines for linewise
+    pub fn span_at(&self, position: usize) -> Option<&Span> {
+        let mut start = 0;
+        for span in self.spans.iter() {
+            if position < start + span.len {
+                return Some(span);
+            }
+            start += span.len;
+        }
+        None
+    }
+
+    // follow the states the server accepted since the last call
+    pub fn catch_up(&mut self, server: &Server<O>) {
+        for state in server.states_since(&self.id) {
+            self.spans = state.diff.attribute(&self.spans, &state.metadata.author, &state.id);
+            self.id = state.id.clone();
+        }
+    }
+}
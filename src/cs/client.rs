@@ -1,7 +1,11 @@
 use std::mem::replace;
+use std::fmt::Display;
 
 use super::*;
 use super::super::Operation;
+use super::presence::{Presence, Transform};
+
+use serde::{Deserialize, Serialize};
 
 extern crate failure;
 use failure::{Error, Fail};
@@ -9,15 +13,18 @@ use failure::{Error, Fail};
 extern crate futures;
 use self::futures::Future;
 use self::futures::FutureExt;
+use self::futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 
 pub trait Connection<O: Operation> {
     type Error: Fail;
-    type Output: Future<Item = (Id, O), Error = Self::Error> + 'static;
+    type Output: Future<Item = Patch<O>, Error = Self::Error> + 'static;
     type StateFuture: Future<Item = State<O>, Error = Self::Error> + 'static;
 
+    // the id the server issued for this connection
+    fn client_id(&self) -> ClientId;
     fn get_latest_state(&self) -> Self::StateFuture;
     fn get_patch_since(&self, since_id: &Id) -> Self::Output;
-    fn send_operation(&self, base_id: Id, operation: O) -> Self::Output;
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output;
 }
 
 impl<O: Operation, C: Connection<O> + ?Sized> Connection<O> for Box<C> {
@@ -25,6 +32,10 @@ impl<O: Operation, C: Connection<O> + ?Sized> Connection<O> for Box<C> {
     type Output = C::Output;
     type StateFuture = C::StateFuture;
 
+    fn client_id(&self) -> ClientId {
+        (**self).client_id()
+    }
+
     fn get_latest_state(&self) -> Self::StateFuture {
         (**self).get_latest_state()
     }
@@ -33,8 +44,8 @@ impl<O: Operation, C: Connection<O> + ?Sized> Connection<O> for Box<C> {
         (**self).get_patch_since(since_id)
     }
 
-    fn send_operation(&self, base_id: Id, operation: O) -> Self::Output {
-        (**self).send_operation(base_id, operation)
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        (**self).send_operation(submission)
     }
 }
 
@@ -43,6 +54,10 @@ impl<'c, O: Operation, C: Connection<O> + ?Sized + 'c> Connection<O> for &'c C {
     type Output = C::Output;
     type StateFuture = C::StateFuture;
 
+    fn client_id(&self) -> ClientId {
+        (*self).client_id()
+    }
+
     fn get_latest_state(&self) -> Self::StateFuture {
         (*self).get_latest_state()
     }
@@ -51,17 +66,31 @@ impl<'c, O: Operation, C: Connection<O> + ?Sized + 'c> Connection<O> for &'c C {
         (*self).get_patch_since(since_id)
     }
 
-    fn send_operation(&self, base_id: Id, operation: O) -> Self::Output {
-        (*self).send_operation(base_id, operation)
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        (*self).send_operation(submission)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClientState<T> {
     id: Id,
     content: T,
 }
 
+// everything a client needs to resume editing, e.g. after being stored on disk while offline
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClientSnapshot<O: Operation> {
+    #[serde(bound(serialize = "O::Target: Serialize",
+                  deserialize = "O::Target: Deserialize<'de>"))]
+    base_state: ClientState<O::Target>,
+    #[serde(bound(serialize = "O: Serialize", deserialize = "O: Deserialize<'de>"))]
+    sent_diff: Option<O>,
+    #[serde(bound(serialize = "O: Serialize", deserialize = "O: Deserialize<'de>"))]
+    current_diff: Option<O>,
+    client_id: ClientId,
+    seq: usize,
+}
+
 #[derive(Debug, Fail)]
 pub enum ClientError {
     #[fail(display = "Error occured in connection: {}", _0)]
@@ -70,19 +99,50 @@ pub enum ClientError {
     Syncing,
     #[fail(display = "Client not connected any more: {}", _0)]
     NotConnected(String),
+    #[fail(display = "No operation is waiting for response")]
+    NotWaiting,
+    #[fail(display = "Content diverged from the server")]
+    Diverged,
+    #[fail(display = "{}", _0)]
+    Rejected(Rejection),
 }
 
+// notifications for editors displaying the client's content
+#[derive(Clone, Debug)]
+pub enum ClientEvent<O> {
+    // a remote operation transformed to be applied to unsynced_content
+    Operation(O),
+    Buffering,
+    WaitingForResponse,
+    // a request to the server failed
+    Error(String),
+    // the server rejected the sent operation, which is undone by the preceding Operation
+    Rejected(Rejection),
+    // the content was replaced with the server's after a divergence, or after a rejection the
+    // server could not undo. the unsynced operations are dropped, read the new content from
+    // the client
+    Reset,
+}
+
+// seq is the sequence number of the in-flight submission while waiting for response,
+// and the last used one while buffering
 pub enum Client<O: Operation, C: Connection<O>> {
     WaitingForResponse {
         base_state: ClientState<O::Target>,
         sent_diff: O,
         current_diff: Option<O>,
         connection: C,
+        client_id: ClientId,
+        seq: usize,
+        listeners: Vec<UnboundedSender<ClientEvent<O>>>,
     },
     Buffering {
         base_state: ClientState<O::Target>,
         current_diff: Option<O>,
         connection: C,
+        client_id: ClientId,
+        seq: usize,
+        listeners: Vec<UnboundedSender<ClientEvent<O>>>,
     },
     Error(String),
 }
@@ -98,11 +158,81 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
                         id: state.id,
                         content: state.content,
                     },
+                    client_id: connection.client_id(),
+                    seq: 0,
                     connection: connection,
+                    listeners: vec![],
                 }),
         )
     }
 
+    // restore a client from a snapshot
+    // call resync to catch up with the server before sending operations
+    pub fn restore(snapshot: ClientSnapshot<O>, connection: C) -> Self {
+        let ClientSnapshot {
+            base_state,
+            sent_diff,
+            current_diff,
+            client_id,
+            seq,
+        } = snapshot;
+
+        if let Some(sent_diff) = sent_diff {
+            Client::WaitingForResponse {
+                base_state,
+                sent_diff,
+                current_diff,
+                connection,
+                client_id,
+                seq,
+                listeners: vec![],
+            }
+        } else {
+            Client::Buffering {
+                base_state,
+                current_diff,
+                connection,
+                client_id,
+                seq,
+                listeners: vec![],
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Result<ClientSnapshot<O>, String> {
+        use self::Client::*;
+        match *self {
+            WaitingForResponse {
+                ref base_state,
+                ref sent_diff,
+                ref current_diff,
+                ref client_id,
+                seq,
+                ..
+            } => Ok(ClientSnapshot {
+                base_state: base_state.clone(),
+                sent_diff: Some(sent_diff.clone()),
+                current_diff: current_diff.clone(),
+                client_id: client_id.clone(),
+                seq: seq,
+            }),
+            Buffering {
+                ref base_state,
+                ref current_diff,
+                ref client_id,
+                seq,
+                ..
+            } => Ok(ClientSnapshot {
+                base_state: base_state.clone(),
+                sent_diff: None,
+                current_diff: current_diff.clone(),
+                client_id: client_id.clone(),
+                seq: seq,
+            }),
+            Error(ref s) => Err(s.clone()),
+        }
+    }
+
     pub fn current_content(&self) -> Result<O::Target, String> {
         use self::Client::*;
         match *self {
@@ -118,10 +248,18 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
         match *self {
             WaitingForResponse {
                 ref base_state,
+                ref sent_diff,
                 ref current_diff,
                 ..
+            } => {
+                let content = sent_diff.apply(&base_state.content);
+                if let Some(ref current) = *current_diff {
+                    Ok(current.apply(&content))
+                } else {
+                    Ok(content)
+                }
             }
-            | Buffering {
+            Buffering {
                 ref base_state,
                 ref current_diff,
                 ..
@@ -136,6 +274,83 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
         }
     }
 
+    // tie presence on the unsynced content to the state the client is based on, to be sent to
+    // a PresenceHub. it is moved back across the unsynced operations by undoing them, so e.g.
+    // a cursor in unsynced text moves to where the text is inserted.
+    // None if the presence does not make sense on that state. operations which can not be
+    // inverted can only express presence while the client is synced
+    pub fn presence<P: Transform<O>>(&self, data: P) -> Result<Option<Presence<P>>, ClientError> {
+        use self::Client::*;
+        use self::ClientError::*;
+
+        let (base_state, sent_diff, current_diff, client_id) = match *self {
+            Error(ref s) => return Err(NotConnected(s.clone())),
+            WaitingForResponse {
+                ref base_state,
+                ref sent_diff,
+                ref current_diff,
+                ref client_id,
+                ..
+            } => (base_state, Some(sent_diff), current_diff, client_id),
+            Buffering {
+                ref base_state,
+                ref current_diff,
+                ref client_id,
+                ..
+            } => (base_state, None, current_diff, client_id),
+        };
+
+        // the diffs with the contents they are applied to, undone from the latest
+        let mut diffs = vec![];
+        let mut content = base_state.content.clone();
+        for diff in sent_diff.into_iter().chain(current_diff.as_ref()) {
+            let applied = diff.apply(&content);
+            diffs.push((diff, content));
+            content = applied;
+        }
+        let mut data = Some(data);
+        for (diff, content) in diffs.into_iter().rev() {
+            let undo = diff.invert(&content).ok_or(Syncing)?;
+            data = data.and_then(|data| data.transform(&undo));
+        }
+
+        Ok(data.map(|data| Presence {
+            client_id: client_id.clone(),
+            id: base_state.id.clone(),
+            data: data,
+        }))
+    }
+
+    // place presence of another client on the unsynced content
+    // None if the presence is not on the state the client is based on
+    pub fn remote_presence<P: Transform<O>>(&self, presence: Presence<P>) -> Option<P> {
+        use self::Client::*;
+
+        let (base_state, sent_diff, current_diff) = match *self {
+            WaitingForResponse {
+                ref base_state,
+                ref sent_diff,
+                ref current_diff,
+                ..
+            } => (base_state, Some(sent_diff), current_diff),
+            Buffering {
+                ref base_state,
+                ref current_diff,
+                ..
+            } => (base_state, None, current_diff),
+            Error(_) => return None,
+        };
+        if presence.id != base_state.id {
+            return None;
+        }
+
+        let mut data = presence.data;
+        for diff in sent_diff.into_iter().chain(current_diff.as_ref()) {
+            data = data.transform(diff)?;
+        }
+        Some(data)
+    }
+
     pub fn push_operation(&mut self, operation: O) {
         use self::Client::*;
         match *self {
@@ -157,7 +372,9 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
         }
     }
 
-    pub fn send_to_server(&mut self) -> Result<C::Output, String> {
+    pub fn send_to_server(
+        &mut self,
+    ) -> Result<Box<Future<Item = Patch<O>, Error = C::Error>>, String> {
         use self::Client::*;
         if let &mut Buffering {
             current_diff: Some(_),
@@ -168,17 +385,36 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
                 base_state,
                 current_diff,
                 connection,
+                client_id,
+                seq,
+                mut listeners,
             } = replace(self, Error("".into()))
             {
                 let current_diff = current_diff.unwrap();
-                let ret = connection.send_operation(base_state.id.clone(), current_diff.clone());
+                // a new connection may have been issued another id, which starts a new session
+                let (client_id, seq) = if connection.client_id() == client_id {
+                    (client_id, seq + 1)
+                } else {
+                    (connection.client_id(), 1)
+                };
+                let ret = connection.send_operation(Submission {
+                    client_id: client_id.clone(),
+                    seq: seq,
+                    parent: base_state.id.clone(),
+                    diff: current_diff.clone(),
+                    acknowledged: Some(seq - 1),
+                });
+                Self::notify(&mut listeners, ClientEvent::WaitingForResponse);
                 *self = WaitingForResponse {
                     base_state: base_state,
                     current_diff: None,
                     sent_diff: current_diff,
                     connection: connection,
+                    client_id: client_id,
+                    seq: seq,
+                    listeners: listeners,
                 };
-                Ok(ret)
+                Ok(self.report(Box::new(ret)))
             } else {
                 unreachable!();
             }
@@ -189,96 +425,303 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
         }
     }
 
-    pub fn apply_patch(&mut self, latest_id: Id, diff: O) -> Result<(), ClientError> {
+    // send the in-flight operation again, e.g. when its response was lost
+    // the server acknowledges it only once, so the response can be applied as usual
+    pub fn resend(&self) -> Result<C::Output, String> {
+        use self::Client::*;
+        match *self {
+            WaitingForResponse {
+                ref base_state,
+                ref sent_diff,
+                ref connection,
+                ref client_id,
+                seq,
+                ..
+            } => Ok(connection.send_operation(Submission {
+                client_id: client_id.clone(),
+                seq: seq,
+                parent: base_state.id.clone(),
+                diff: sent_diff.clone(),
+                acknowledged: Some(seq - 1),
+            })),
+            _ => Err("client is not waiting for response".into()),
+        }
+    }
+
+    // returns Diverged if the new content does not match the checksum of the server
+    // the client should be reset to the latest state of the server then.
+    // while waiting for response, the patch contains the sent operation and is applied as the
+    // response
+    pub fn apply_patch(&mut self, patch: Patch<O>) -> Result<(), ClientError> {
         use self::Client::*;
         use self::ClientError::*;
 
         match replace(self, Error("".into())) {
             Error(ref s) => Err(NotConnected(s.clone())),
-            WaitingForResponse {
-                base_state,
-                sent_diff,
-                current_diff,
-                connection,
-            } => {
-                let content = sent_diff.compose(diff.clone()).apply(&base_state.content);
-
-                *self = Buffering {
-                    current_diff: current_diff.map(|current| current.transform(diff).0),
-                    base_state: ClientState {
-                        id: latest_id,
-                        content: content,
-                    },
-                    connection: connection,
-                };
-
-                Ok(())
+            client @ WaitingForResponse { .. } => {
+                *self = client;
+                self.apply_response(patch)
             }
             Buffering {
                 mut base_state,
                 mut current_diff,
                 connection,
+                client_id,
+                seq,
+                mut listeners,
             } => {
-                Self::patch(&mut base_state, &mut current_diff, latest_id, diff)?;
+                let Patch {
+                    id: latest_id,
+                    diff,
+                    checksum,
+                    ..
+                } = patch;
+                let local_diff = Self::patch(&mut base_state, &mut current_diff, latest_id, diff)?;
+                let verified = Self::verify(&mut listeners, &base_state.content, checksum);
+                Self::notify(&mut listeners, ClientEvent::Operation(local_diff));
                 *self = Buffering {
                     base_state,
                     current_diff,
                     connection,
+                    client_id,
+                    seq,
+                    listeners,
                 };
-                Ok(())
+                verified
             }
         }
     }
 
-    pub fn apply_response(&mut self, id: Id, op: O) -> Result<(), C::Error> {
+    // returns Diverged in the same way as apply_patch
+    // returns Rejected if the server rejected the sent operation. it is rolled back then, and
+    // the operations pushed after it are transformed as if it had never been made. if the
+    // server could not undo it, they are dropped along with it and Reset is notified
+    pub fn apply_response(&mut self, patch: Patch<O>) -> Result<(), ClientError> {
         use self::Client::*;
+
+        let Patch {
+            id,
+            diff: op,
+            checksum,
+            rejection,
+            from_parent,
+            ..
+        } = patch;
         match replace(self, Error("".into())) {
             WaitingForResponse {
                 base_state,
                 sent_diff,
                 current_diff,
                 connection,
+                client_id,
+                seq,
+                mut listeners,
             } => {
-                let content = sent_diff.compose(op.clone()).apply(&base_state.content);
+                let (content, current_diff, event) = if from_parent {
+                    (op.apply(&base_state.content), None, ClientEvent::Reset)
+                } else {
+                    let content = sent_diff.compose(op.clone()).apply(&base_state.content);
+                    let (current_diff, local_diff) = Self::rebase(current_diff, op);
+                    (content, current_diff, ClientEvent::Operation(local_diff))
+                };
+                let verified = Self::verify(&mut listeners, &content, checksum);
 
+                Self::notify(&mut listeners, event);
+                if let Some(ref rejection) = rejection {
+                    Self::notify(&mut listeners, ClientEvent::Rejected(rejection.clone()));
+                }
+                Self::notify(&mut listeners, ClientEvent::Buffering);
                 *self = Buffering {
-                    current_diff: current_diff.map(|diff| diff.transform(op).0),
+                    current_diff: current_diff,
                     base_state: ClientState {
                         id: id,
                         content: content,
                     },
                     connection: connection,
+                    client_id: client_id,
+                    seq: seq,
+                    listeners: listeners,
                 };
 
+                verified?;
+                rejection.map_or(Ok(()), |rejection| Err(ClientError::Rejected(rejection)))
+            }
+            Error(s) => {
+                let error = ClientError::NotConnected(s.clone());
+                *self = Error(s);
+                Err(error)
+            }
+            client => {
+                *self = client;
+                Err(ClientError::NotWaiting)
+            }
+        }
+    }
+
+    // compare the content with the checksum of the server, when both are known
+    fn verify(
+        listeners: &mut Vec<UnboundedSender<ClientEvent<O>>>,
+        content: &O::Target,
+        checksum: Option<u64>,
+    ) -> Result<(), ClientError> {
+        match (checksum, O::checksum(content)) {
+            (Some(expected), Some(actual)) if expected != actual => {
+                let error = ClientError::Diverged;
+                Self::notify(listeners, ClientEvent::Error(error.to_string()));
+                Err(error)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // request the latest state of the server to be passed to reset
+    pub fn reload(&self) -> Box<Future<Item = State<O>, Error = ClientError>> {
+        use self::Client::*;
+        use self::ClientError::*;
+        use self::futures::future::err;
+
+        match *self {
+            Error(ref s) => Box::new(err(NotConnected(s.clone()))),
+            WaitingForResponse { ref connection, .. } | Buffering { ref connection, .. } => self
+                .report(Box::new(
+                    connection
+                        .get_latest_state()
+                        .map_err(Into::into)
+                        .map_err(ConnectionError),
+                )),
+        }
+    }
+
+    // replace the content with the state of the server, e.g. after a divergence
+    // the unsynced operations are dropped since they are based on the wrong content, including
+    // the one waiting for a response. the response to it is not expected any more
+    pub fn reset(&mut self, state: State<O>) -> Result<(), ClientError> {
+        use self::Client::*;
+        use self::ClientError::*;
+
+        let waiting = match *self {
+            WaitingForResponse { .. } => true,
+            _ => false,
+        };
+        match replace(self, Error("".into())) {
+            Error(s) => {
+                *self = Error(s.clone());
+                Err(NotConnected(s))
+            }
+            WaitingForResponse {
+                connection,
+                client_id,
+                seq,
+                mut listeners,
+                ..
+            }
+            | Buffering {
+                connection,
+                client_id,
+                seq,
+                mut listeners,
+                ..
+            } => {
+                Self::notify(&mut listeners, ClientEvent::Reset);
+                if waiting {
+                    Self::notify(&mut listeners, ClientEvent::Buffering);
+                }
+                *self = Buffering {
+                    base_state: ClientState {
+                        id: state.id,
+                        content: state.content,
+                    },
+                    current_diff: None,
+                    connection: connection,
+                    client_id: client_id,
+                    seq: seq,
+                    listeners: listeners,
+                };
                 Ok(())
             }
-            _ => unreachable!(),
         }
     }
 
+    // returns the diff to be applied to the local view
     fn patch<'a>(
         base_state: &'a mut ClientState<O::Target>,
         current_diff: &'a mut Option<O>,
         latest_id: Id,
         diff: O,
-    ) -> Result<(), ClientError> {
-        let content;
-        if let Some(current) = replace(current_diff, None) {
-            let (current, _) = current.transform(diff.clone());
-            content = diff.compose(current).apply(&base_state.content);
-        } else {
-            content = diff.apply(&base_state.content);
-        }
+    ) -> Result<O, ClientError> {
+        let content = diff.apply(&base_state.content);
+        let (current, local_diff) = Self::rebase(replace(current_diff, None), diff);
+        *current_diff = current;
 
         *base_state = ClientState {
             id: latest_id,
             content: content,
         };
 
-        Ok(())
+        Ok(local_diff)
+    }
+
+    // transform a remote diff and the local buffer against each other
+    // returns the transformed buffer and the diff to be applied to the local view
+    fn rebase(current_diff: Option<O>, diff: O) -> (Option<O>, O) {
+        if let Some(current) = current_diff {
+            let (current, diff) = current.transform(diff);
+            (Some(current), diff)
+        } else {
+            (None, diff)
+        }
     }
 
-    pub fn send_get_patch(&self) -> Box<Future<Item = (Id, O), Error = ClientError>> {
+    // receive remote operations and sync state changes
+    // the stream ends when the client is dropped or enters the error state
+    pub fn subscribe(&mut self) -> UnboundedReceiver<ClientEvent<O>> {
+        use self::Client::*;
+
+        let (sender, receiver) = unbounded();
+        match *self {
+            WaitingForResponse {
+                ref mut listeners,
+                ..
+            }
+            | Buffering {
+                ref mut listeners,
+                ..
+            } => listeners.push(sender),
+            Error(_) => {}
+        }
+        receiver
+    }
+
+    fn notify(listeners: &mut Vec<UnboundedSender<ClientEvent<O>>>, event: ClientEvent<O>) {
+        listeners.retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+    }
+
+    // notify the listeners when the request fails
+    fn report<T, E>(
+        &self,
+        future: Box<Future<Item = T, Error = E>>,
+    ) -> Box<Future<Item = T, Error = E>>
+    where
+        T: 'static,
+        E: Display + 'static,
+    {
+        use self::Client::*;
+
+        let listeners = match *self {
+            WaitingForResponse { ref listeners, .. } | Buffering { ref listeners, .. } => {
+                listeners.clone()
+            }
+            Error(_) => vec![],
+        };
+        Box::new(future.map_err(move |error| {
+            for listener in listeners.iter() {
+                let _ = listener.unbounded_send(ClientEvent::Error(error.to_string()));
+            }
+            error
+        }))
+    }
+
+    pub fn send_get_patch(&self) -> Box<Future<Item = Patch<O>, Error = ClientError>> {
         use self::Client::*;
         use self::ClientError::*;
         use self::futures::future::err;
@@ -291,13 +734,80 @@ impl<'c, O: Operation + 'static, C: Connection<O> + 'c> Client<O, C> {
                 ref connection,
                 ..
             } => {
-                Box::new(
+                self.report(Box::new(
                     connection
                         .get_patch_since(&base_state.id)
                         .map_err(Into::into)
                         .map_err(ConnectionError),
-                ) // should we change self to Error?
+                )) // should we change self to Error?
+            }
+        }
+    }
+
+    // replace the connection, e.g. after a request on the old one failed, and resync
+    // applying the returned patch brings the client back to buffering state with its local
+    // operations kept.
+    // the operation in flight is resent with the client id it was sent with, and the id
+    // issued by the new connection is used for the following ones.
+    // a client in the error state has nothing to keep, so it starts over from the empty first
+    // state of the server, which never fails. its listeners were dropped when it entered the
+    // error state, ending their streams, so they have to subscribe again after reconnecting
+    pub fn reconnect(
+        &mut self,
+        connection: C,
+    ) -> Result<Box<Future<Item = Patch<O>, Error = ClientError>>, ClientError> {
+        use self::Client::*;
+
+        match *self {
+            Error(_) => {
+                *self = Buffering {
+                    base_state: ClientState {
+                        id: Id(0),
+                        content: O::Target::default(),
+                    },
+                    current_diff: None,
+                    client_id: connection.client_id(),
+                    seq: 0,
+                    connection: connection,
+                    listeners: vec![],
+                };
             }
+            WaitingForResponse {
+                connection: ref mut old_connection,
+                ..
+            } => *old_connection = connection,
+            Buffering {
+                connection: ref mut old_connection,
+                ref mut client_id,
+                ref mut seq,
+                ..
+            } => {
+                if connection.client_id() != *client_id {
+                    *client_id = connection.client_id();
+                    *seq = 0;
+                }
+                *old_connection = connection;
+            }
+        }
+
+        Ok(self.resync())
+    }
+
+    // request a patch to be passed to apply_patch that catches up with the server
+    // an operation in flight is resubmitted, so it is not applied twice
+    pub fn resync(&self) -> Box<Future<Item = Patch<O>, Error = ClientError>> {
+        use self::Client::*;
+        use self::ClientError::*;
+        use self::futures::future::err;
+
+        match *self {
+            WaitingForResponse { .. } => match self.resend() {
+                Ok(resent) => {
+                    self.report(Box::new(resent.map_err(Into::into).map_err(ConnectionError)))
+                }
+                Err(s) => Box::new(err(NotConnected(s))),
+            },
+            _ => self.send_get_patch(),
         }
     }
 }
@@ -0,0 +1,283 @@
+use super::*;
+use super::super::Operation;
+use super::client::{Client, ClientError, ClientEvent, Connection};
+
+use std::mem::replace;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+extern crate futures;
+use self::futures::{Async, Future, FutureExt, Poll};
+use self::futures::channel::mpsc::UnboundedReceiver;
+use self::futures::task::{Context, Waker};
+
+struct Shared<O: Operation, C: Connection<O>> {
+    client: Client<O, C>,
+    // when the buffer was last modified through a handle
+    last_edit: Option<Instant>,
+    waker: Option<Waker>,
+}
+
+enum Response<O: Operation> {
+    Patch(Patch<O>),
+    State(State<O>),
+}
+
+// a thread which wakes the driver at the deadline
+// a new deadline replaces the pending one, and the thread exits when the driver is dropped
+struct Timer {
+    sender: Sender<(Instant, Waker)>,
+}
+
+impl Timer {
+    fn new() -> Self {
+        let (sender, receiver) = channel::<(Instant, Waker)>();
+        thread::spawn(move || {
+            let mut pending: Option<(Instant, Waker)> = None;
+            loop {
+                let received = match pending {
+                    Some((deadline, _)) => {
+                        let now = Instant::now();
+                        if deadline <= now {
+                            pending.take().unwrap().1.wake();
+                            continue;
+                        }
+                        receiver.recv_timeout(deadline - now)
+                    }
+                    None => receiver
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(next) => pending = Some(next),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+        Timer { sender: sender }
+    }
+
+    fn wake_at(&self, deadline: Instant, waker: Waker) {
+        // the thread lives as long as the sender
+        let _ = self.sender.send((deadline, waker));
+    }
+}
+
+// a future which keeps a client in sync with the server
+// operations pushed through the handles are sent once no edit happened for the debounce
+// duration, and patches are requested every poll interval.
+// it resolves to the client when all the handles are dropped and the buffer is synced.
+// when the client diverges from the server, it is reset to the latest state of the server.
+// operations rejected by the server are rolled back and reported to the subscribers, and the
+// driver fails with NotConnected if the client is in the error state
+pub struct Driver<O: Operation, C: Connection<O>> {
+    shared: Rc<RefCell<Shared<O, C>>>,
+    request: Option<Box<Future<Item = Response<O>, Error = ClientError>>>,
+    diverged: bool,
+    debounce: Duration,
+    poll_interval: Duration,
+    next_poll: Instant,
+    timer: Timer,
+    // the wake up already scheduled
+    deadline: Option<Instant>,
+}
+
+// the editor side of a driver
+pub struct DriverHandle<O: Operation, C: Connection<O>> {
+    shared: Rc<RefCell<Shared<O, C>>>,
+}
+
+impl<O: Operation + 'static, C: Connection<O>> Driver<O, C> {
+    pub fn new(
+        client: Client<O, C>,
+        debounce: Duration,
+        poll_interval: Duration,
+    ) -> (Self, DriverHandle<O, C>) {
+        let shared = Rc::new(RefCell::new(Shared {
+            client: client,
+            last_edit: None,
+            waker: None,
+        }));
+        let driver = Driver {
+            shared: shared.clone(),
+            request: None,
+            diverged: false,
+            debounce: debounce,
+            poll_interval: poll_interval,
+            next_poll: Instant::now(),
+            timer: Timer::new(),
+            deadline: None,
+        };
+
+        (driver, DriverHandle { shared: shared })
+    }
+
+    // start a request if something is due
+    // returns false if there is nothing to do for now
+    fn start_request(&mut self, now: Instant, patch_requested: &mut bool) -> bool {
+        let mut guard = self.shared.borrow_mut();
+        let shared = &mut *guard;
+        let debounce = self.debounce;
+
+        let request: Box<Future<Item = _, Error = _>> = match shared.client {
+            Client::Buffering { .. } if self.diverged => {
+                if now < self.next_poll {
+                    return false;
+                }
+                self.next_poll = now + self.poll_interval;
+                Box::new(shared.client.reload().map(Response::State))
+            }
+            Client::WaitingForResponse { .. } if now >= self.next_poll => {
+                // the previous request failed
+                self.next_poll = now + self.poll_interval;
+                Box::new(shared.client.resync().map(Response::Patch))
+            }
+            Client::Buffering {
+                current_diff: Some(_),
+                ..
+            } if shared.last_edit.map_or(true, |edit| edit + debounce <= now) =>
+            {
+                shared.last_edit = None;
+                match shared.client.send_to_server() {
+                    Ok(future) => Box::new(
+                        future
+                            .map(Response::Patch)
+                            .map_err(Into::into)
+                            .map_err(ClientError::ConnectionError),
+                    ),
+                    Err(_) => return false,
+                }
+            }
+            Client::Buffering { .. } if now >= self.next_poll && !*patch_requested => {
+                *patch_requested = true;
+                self.next_poll = now + self.poll_interval;
+                Box::new(shared.client.send_get_patch().map(Response::Patch))
+            }
+            _ => return false,
+        };
+
+        self.request = Some(request);
+        true
+    }
+
+    fn is_synced(&self) -> bool {
+        match self.shared.borrow().client {
+            Client::Buffering {
+                current_diff: None,
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+
+    fn schedule(&mut self, cx: &mut Context, now: Instant) {
+        let mut deadline = self.next_poll;
+        if let Some(edit) = self.shared.borrow().last_edit {
+            deadline = deadline.min(edit + self.debounce);
+        }
+
+        if self.deadline
+            .map_or(false, |scheduled| now < scheduled && scheduled <= deadline)
+        {
+            return;
+        }
+
+        self.deadline = Some(deadline);
+        self.timer.wake_at(deadline, cx.waker().clone());
+    }
+}
+
+impl<O: Operation + 'static, C: Connection<O>> Future for Driver<O, C> {
+    type Item = Client<O, C>;
+    type Error = ClientError;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Item, Self::Error> {
+        let mut patch_requested = false;
+
+        loop {
+            if let Some(mut request) = self.request.take() {
+                match request.poll(cx) {
+                    Ok(Async::Ready(Response::Patch(patch))) => {
+                        match self.shared.borrow_mut().client.apply_patch(patch) {
+                            Err(ClientError::Diverged) => self.diverged = true,
+                            // subscribers are notified of the rejection
+                            Err(ClientError::Rejected(_)) => {}
+                            result => result?,
+                        }
+                    }
+                    Ok(Async::Ready(Response::State(state))) => {
+                        self.shared.borrow_mut().client.reset(state)?;
+                        self.diverged = false;
+                    }
+                    Ok(Async::Pending) => {
+                        self.request = Some(request);
+                        break;
+                    }
+                    // subscribers are notified of the error, retry on the next poll
+                    Err(_) => self.next_poll = Instant::now() + self.poll_interval,
+                }
+            }
+
+            if !self.start_request(Instant::now(), &mut patch_requested) {
+                break;
+            }
+        }
+
+        if let Client::Error(ref s) = self.shared.borrow().client {
+            return Err(ClientError::NotConnected(s.clone()));
+        }
+
+        if self.request.is_none() && Rc::strong_count(&self.shared) == 1 && !self.diverged
+            && self.is_synced()
+        {
+            let client = replace(
+                &mut self.shared.borrow_mut().client,
+                Client::Error("driver finished".into()),
+            );
+            return Ok(Async::Ready(client));
+        }
+
+        self.shared.borrow_mut().waker = Some(cx.waker().clone());
+        if self.request.is_none() {
+            self.schedule(cx, Instant::now());
+        }
+
+        Ok(Async::Pending)
+    }
+}
+
+impl<O: Operation + 'static, C: Connection<O>> DriverHandle<O, C> {
+    pub fn push_operation(&self, operation: O) {
+        let mut shared = self.shared.borrow_mut();
+        shared.client.push_operation(operation);
+        shared.last_edit = Some(Instant::now());
+        if let Some(ref waker) = shared.waker {
+            waker.wake();
+        }
+    }
+
+    pub fn current_content(&self) -> Result<O::Target, String> {
+        self.shared.borrow().client.current_content()
+    }
+
+    pub fn unsynced_content(&self) -> Result<O::Target, String> {
+        self.shared.borrow().client.unsynced_content()
+    }
+
+    pub fn subscribe(&self) -> UnboundedReceiver<ClientEvent<O>> {
+        self.shared.borrow_mut().client.subscribe()
+    }
+}
+
+impl<O: Operation, C: Connection<O>> Drop for DriverHandle<O, C> {
+    fn drop(&mut self) {
+        // let the driver notice that it may finish
+        if let Some(ref waker) = self.shared.borrow().waker {
+            waker.wake();
+        }
+    }
+}
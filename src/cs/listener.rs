@@ -0,0 +1,187 @@
+// accepts connections of a transport, e.g. TCP or WebSocket, and serves them with a Server
+//
+// every connection is served by a thread of its own. the new state of the server is pushed to
+// every client after each request modifying it. pushes are queued for each connection and
+// written by its thread between requests, so that a connection is only written by one thread
+
+use super::*;
+use super::super::Operation;
+use super::protocol::{dispatch, Message, Request};
+use super::server::{self, Server};
+use super::shared_server::SharedServer;
+
+use std::io;
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+// how long a connection waits for a request before writing the queued pushes
+pub const POLL_INTERVAL_MS: u64 = 20;
+
+// what a connection received
+pub enum Incoming<O> {
+    // a request, or why it could not be parsed
+    Request(Result<Request<O>, String>),
+    // nothing arrived within the poll interval
+    Idle,
+    Closed,
+}
+
+// the server side of a protocol over TCP
+pub trait Transport<O: Operation>: Sized {
+    type Error: From<io::Error>;
+
+    // set up an accepted connection, e.g. with a handshake
+    fn open(stream: TcpStream) -> Result<Self, Self::Error>;
+
+    // returns Idle if the read timed out, keeping what was read so far for the next call
+    fn receive(&mut self) -> Result<Incoming<O>, Self::Error>;
+
+    fn send(&mut self, message: &Message<O>) -> Result<(), Self::Error>;
+}
+
+fn poisoned(s: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, s)
+}
+
+fn lock_peers<O: Operation>(
+    peers: &Mutex<Vec<Peer<O>>>,
+) -> io::Result<MutexGuard<Vec<Peer<O>>>> {
+    peers
+        .lock()
+        .map_err(|_| poisoned("the peers were poisoned by a panicked thread".into()))
+}
+
+// the queue of the states to be pushed to a connection
+struct Peer<O: Operation> {
+    client_id: ClientId,
+    sender: Sender<State<O>>,
+}
+
+impl<O: Operation> server::Connection<O> for Peer<O> {
+    fn send_state(&mut self, state: &State<O>) {
+        // the receiver is dropped only after the peer is removed
+        let _ = self.sender.send(state.clone());
+    }
+}
+
+pub struct Listener<O: Operation, T> {
+    listener: TcpListener,
+    server: SharedServer<O>,
+    peers: Arc<Mutex<Vec<Peer<O>>>>,
+    transport: PhantomData<fn() -> T>,
+}
+
+impl<O, T> Listener<O, T>
+where
+    O: Operation + Send + 'static,
+    O::Target: Send,
+    T: Transport<O> + 'static,
+    T::Error: Send + 'static,
+{
+    pub fn bind<A: ToSocketAddrs>(addr: A, server: Server<O>) -> io::Result<Self> {
+        Ok(Listener {
+            listener: TcpListener::bind(addr)?,
+            server: SharedServer::new(server),
+            peers: Arc::new(Mutex::new(vec![])),
+            transport: PhantomData,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // the server as its mutex, which the connections lock for each request
+    pub fn server(&self) -> Arc<Mutex<Server<O>>> {
+        self.server.mutex()
+    }
+
+    pub fn shared_server(&self) -> SharedServer<O> {
+        self.server.clone()
+    }
+
+    // accept a connection and serve it in a new thread
+    pub fn accept(&self) -> io::Result<thread::JoinHandle<Result<(), T::Error>>> {
+        let (stream, _) = self.listener.accept()?;
+        let server = self.server.clone();
+        let peers = self.peers.clone();
+        Ok(thread::spawn(move || Self::serve(stream, &server, &peers)))
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        loop {
+            self.accept()?;
+        }
+    }
+
+    fn serve(
+        stream: TcpStream,
+        server: &SharedServer<O>,
+        peers: &Mutex<Vec<Peer<O>>>,
+    ) -> Result<(), T::Error> {
+        // the clone shares the timeout with the stream. it is set after opening so that
+        // handshakes can block
+        let socket = stream.try_clone()?;
+        let mut transport = T::open(stream)?;
+        socket.set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL_MS)))?;
+
+        let client_id = server.lock().map_err(poisoned)?.new_client_id();
+        transport.send(&Message::Hello(client_id.clone()))?;
+
+        // registered before taking the state, so that no later state is missed
+        let (sender, receiver) = channel();
+        let mut peer = Peer {
+            client_id: client_id.clone(),
+            sender: sender.clone(),
+        };
+        lock_peers(peers)?.push(Peer {
+            client_id: client_id.clone(),
+            sender: sender,
+        });
+        let state = server.current_state().map_err(poisoned)?;
+        server::Connection::send_state(&mut peer, &state);
+
+        let result = Self::handle(&mut transport, &receiver, server, peers);
+        lock_peers(peers)?.retain(|peer| peer.client_id != client_id);
+        result
+    }
+
+    fn handle(
+        transport: &mut T,
+        pushes: &Receiver<State<O>>,
+        server: &SharedServer<O>,
+        peers: &Mutex<Vec<Peer<O>>>,
+    ) -> Result<(), T::Error> {
+        loop {
+            for state in pushes.try_iter() {
+                transport.send(&Message::Push(state))?;
+            }
+
+            let request = match transport.receive()? {
+                Incoming::Request(request) => request,
+                Incoming::Idle => continue,
+                Incoming::Closed => return Ok(()),
+            };
+
+            // a malformed request is replied too, so that the replies stay in order
+            // the lock is released before writing to the connections
+            let (reply, modified) = {
+                let mut server = server.lock().map_err(poisoned)?;
+                let latest_id = server.current_state().id.clone();
+                let reply = request.and_then(|request| dispatch(&mut *server, request));
+                let state = server.current_state();
+                (reply, if state.id != latest_id { Some(state.clone()) } else { None })
+            };
+            transport.send(&Message::Reply(reply))?;
+            if let Some(state) = modified {
+                for peer in lock_peers(peers)?.iter_mut() {
+                    server::Connection::send_state(peer, &state);
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use Operation;
-use super::{Id, State};
+use super::{ClientId, Id, Patch, State, Submission};
 use super::server;
 use server::Server;
 use super::client;
@@ -9,11 +9,12 @@ use futures::Future;
 use std::rc::Rc;
 use std::cell::RefCell;
 
-pub struct MockConnection<O: Operation>(Rc<RefCell<Server<O>>>);
+pub struct MockConnection<O: Operation>(Rc<RefCell<Server<O>>>, ClientId);
 
 impl<O: Operation> MockConnection<O> {
     pub fn new(server: Rc<RefCell<Server<O>>>) -> Self {
-        MockConnection(server)
+        let client_id = server.borrow_mut().new_client_id();
+        MockConnection(server, client_id)
     }
 }
 
@@ -33,9 +34,13 @@ impl From<String> for MockConnectionError {
 
 impl<O: Operation + 'static> client::Connection<O> for MockConnection<O> {
     type Error = MockConnectionError;
-    type Output = Box<Future<Item = (Id, O), Error = Self::Error>>;
+    type Output = Box<Future<Item = Patch<O>, Error = Self::Error>>;
     type StateFuture = Box<Future<Item = State<O>, Error = Self::Error>>;
 
+    fn client_id(&self) -> ClientId {
+        self.1.clone()
+    }
+
     fn get_latest_state(&self) -> Self::StateFuture {
         use futures::future::ok;
 
@@ -50,10 +55,10 @@ impl<O: Operation + 'static> client::Connection<O> for MockConnection<O> {
         Box::new(result(server.get_patch(since_id).map_err(Into::into)))
     }
 
-    fn send_operation(&self, parent: Id, op: O) -> Self::Output {
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
         use futures::future::result;
 
         let mut server = self.0.borrow_mut();
-        Box::new(result(server.modify(parent, op).map_err(Into::into)))
+        Box::new(result(server.submit(submission).map_err(Into::into)))
     }
 }
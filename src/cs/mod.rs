@@ -2,13 +2,32 @@ use super::Operation;
 
 pub mod server;
 pub mod client;
+pub mod pipelined_client;
 pub mod mock_connection;
+pub mod shared_server;
+pub mod presence;
+pub mod suggestion;
+pub mod attribution;
+pub mod protocol;
+pub mod listener;
+pub mod tcp_connection;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+pub mod driver;
 
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize, Debug)]
 pub struct Id(pub usize);
 
+// identifies a client session so that the server can recognize resubmissions
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize, Debug)]
+pub struct ClientId(pub usize);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct State<O: Operation> {
     pub parent: Id,
@@ -18,4 +37,79 @@ pub struct State<O: Operation> {
     #[serde(bound(serialize = "O::Target: Serialize",
                   deserialize = "O::Target: Deserialize<'de>"))]
     pub content: O::Target,
+    // the checksum of content, if the operation supports it
+    #[serde(default)]
+    pub checksum: Option<u64>,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+// what the server knows about how a state was made
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    // the user who made the state, if known
+    pub author: Option<String>,
+    // milliseconds since the unix epoch when the server accepted the state
+    pub timestamp: u64,
+    // the client the operation came from, None if the server was modified directly
+    pub session: Option<ClientId>,
+    // anything else the application wants to keep along with the state
+    pub fields: BTreeMap<String, String>,
+}
+
+// the server's response to a submission or a patch request
+// applying diff brings the client to state id, whose content has the checksum
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Patch<O> {
+    pub id: Id,
+    pub diff: O,
+    #[serde(default)]
+    pub checksum: Option<u64>,
+    // the metadata of the states diff brings the client through, oldest first
+    // only the newest ones within the limit of the server are included
+    #[serde(default)]
+    pub metadata: Vec<Metadata>,
+    // set if the server rejected the submitted operation
+    // diff then undoes the operation as well as bringing the client to state id
+    #[serde(default)]
+    pub rejection: Option<Rejection>,
+    // set along with rejection if the operation does not support inversion
+    // diff then applies to the state the operation was based on instead of undoing it, and
+    // the client drops the operation along with the unsynced ones built on it
+    #[serde(default)]
+    pub from_parent: bool,
+}
+
+// why a validator of the server refused an operation
+// code is meant for programs, e.g. "read-only", and reason for users
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Fail)]
+#[fail(display = "operation rejected ({}): {}", code, reason)]
+pub struct Rejection {
+    pub code: String,
+    pub reason: String,
+}
+
+impl Rejection {
+    pub fn new<C: Into<String>, R: Into<String>>(code: C, reason: R) -> Self {
+        Rejection {
+            code: code.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+// an operation sent from a client to the server
+// seq is incremented by the client for each new submission.
+// resending a submission with the same seq does not apply the operation twice
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Submission<O> {
+    pub client_id: ClientId,
+    pub seq: usize,
+    pub parent: Id,
+    pub diff: O,
+    // the seq of the last acknowledgement the client has applied, which tells the server
+    // which of the operations in flight the submission is built on. guessed from parent if
+    // missing, which is ambiguous when a rejection did not change the server
+    #[serde(default)]
+    pub acknowledged: Option<usize>,
 }
@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::mem::replace;
+
+use super::*;
+use super::super::Operation;
+use super::client::{ClientError, Connection};
+
+extern crate futures;
+use self::futures::Future;
+use self::futures::FutureExt;
+
+// a client which can have several operations in flight
+// each operation is sent on top of the unacknowledged ones, which the server recognizes by
+// the client id and seq. responses must be applied in the order the operations were sent.
+// patches can only be applied when nothing is in flight, since they would contain the
+// client's own operations. the responses carry the concurrent changes meanwhile
+pub struct PipelinedClient<O: Operation, C: Connection<O>> {
+    base_id: Id,
+    base_content: O::Target,
+    // sent operations with their seq, transformed so that they apply to the base in order
+    in_flight: VecDeque<(usize, O)>,
+    current_diff: Option<O>,
+    connection: C,
+    client_id: ClientId,
+    // the last used seq
+    seq: usize,
+    max_in_flight: usize,
+}
+
+impl<'c, O: Operation + 'static, C: Connection<O> + 'c> PipelinedClient<O, C> {
+    pub fn with_connection(
+        connection: C,
+        max_in_flight: usize,
+    ) -> Box<Future<Item = Self, Error = C::Error> + 'c> {
+        Box::new(
+            connection
+                .get_latest_state()
+                .map(move |state| PipelinedClient {
+                    base_id: state.id,
+                    base_content: state.content,
+                    in_flight: VecDeque::new(),
+                    current_diff: None,
+                    client_id: connection.client_id(),
+                    seq: 0,
+                    connection: connection,
+                    max_in_flight: max_in_flight,
+                }),
+        )
+    }
+
+    pub fn current_content(&self) -> O::Target {
+        self.base_content.clone()
+    }
+
+    pub fn unsynced_content(&self) -> O::Target {
+        let mut content = self.base_content.clone();
+        for &(_, ref sent) in self.in_flight.iter() {
+            content = sent.apply(&content);
+        }
+        if let Some(ref current) = self.current_diff {
+            content = current.apply(&content);
+        }
+        content
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn push_operation(&mut self, operation: O) {
+        if let Some(current) = replace(&mut self.current_diff, None) {
+            self.current_diff = Some(current.compose(operation));
+        } else {
+            self.current_diff = Some(operation);
+        }
+    }
+
+    pub fn send_to_server(&mut self) -> Result<C::Output, String> {
+        if self.in_flight.len() >= self.max_in_flight {
+            return Err("too many operations in flight".into());
+        }
+
+        if let Some(current) = replace(&mut self.current_diff, None) {
+            let acknowledged = self.seq - self.in_flight.len();
+            self.seq += 1;
+            let ret = self.connection.send_operation(Submission {
+                client_id: self.client_id.clone(),
+                seq: self.seq,
+                parent: self.base_id.clone(),
+                diff: current.clone(),
+                acknowledged: Some(acknowledged),
+            });
+            self.in_flight.push_back((self.seq, current));
+            Ok(ret)
+        } else {
+            Err("client has no diff in buffer".into())
+        }
+    }
+
+    // send the operations in flight again, e.g. when their responses were lost
+    pub fn resend(&self) -> Vec<C::Output> {
+        let acknowledged = self.seq - self.in_flight.len();
+        self.in_flight
+            .iter()
+            .map(|&(seq, ref sent)| {
+                self.connection.send_operation(Submission {
+                    client_id: self.client_id.clone(),
+                    seq: seq,
+                    parent: self.base_id.clone(),
+                    diff: sent.clone(),
+                    acknowledged: Some(acknowledged),
+                })
+            })
+            .collect()
+    }
+
+    // apply the response to the oldest operation in flight
+    // a rejected operation is rolled back, and the rest are transformed as if it had never been
+    // made, in the same way as the server does. if the server could not undo it, the rest and
+    // the buffer are dropped along with it, and their seq are used again. the server answers
+    // the dropped ones with errors.
+    // returns Diverged if the new content does not match the checksum of the server, as Client
+    pub fn apply_response(&mut self, patch: Patch<O>) -> Result<(), ClientError> {
+        let Patch {
+            id,
+            diff: op,
+            checksum,
+            rejection,
+            from_parent,
+            ..
+        } = patch;
+        let (seq, sent) = match self.in_flight.pop_front() {
+            Some(sent) => sent,
+            None => return Err(ClientError::NotWaiting),
+        };
+
+        if from_parent {
+            self.base_content = op.apply(&self.base_content);
+            self.base_id = id;
+            self.in_flight.clear();
+            self.current_diff = None;
+            self.seq = seq;
+
+            self.verify(checksum)?;
+            return rejection.map_or(Ok(()), |rejection| Err(ClientError::Rejected(rejection)));
+        }
+
+        self.base_content = sent.compose(op.clone()).apply(&self.base_content);
+        self.base_id = id;
+
+        // the rest are based on the acknowledged operation
+        let mut diff = op;
+        for &mut (_, ref mut sent) in self.in_flight.iter_mut() {
+            let (sent_, diff_) = sent.clone().transform(diff);
+            *sent = sent_;
+            diff = diff_;
+        }
+        if let Some(current) = replace(&mut self.current_diff, None) {
+            self.current_diff = Some(current.transform(diff).0);
+        }
+
+        self.verify(checksum)?;
+        rejection.map_or(Ok(()), |rejection| Err(ClientError::Rejected(rejection)))
+    }
+
+    // returns Diverged in the same way as apply_response
+    pub fn apply_patch(&mut self, patch: Patch<O>) -> Result<(), ClientError> {
+        let Patch {
+            id: latest_id,
+            diff,
+            checksum,
+            ..
+        } = patch;
+        if !self.in_flight.is_empty() {
+            return Err(ClientError::Syncing);
+        }
+
+        self.base_content = diff.apply(&self.base_content);
+        self.base_id = latest_id;
+        if let Some(current) = replace(&mut self.current_diff, None) {
+            self.current_diff = Some(current.transform(diff).0);
+        }
+
+        self.verify(checksum)
+    }
+
+    // compare the content with the checksum of the server, when both are known
+    fn verify(&self, checksum: Option<u64>) -> Result<(), ClientError> {
+        match (checksum, O::checksum(&self.base_content)) {
+            (Some(expected), Some(actual)) if expected != actual => Err(ClientError::Diverged),
+            _ => Ok(()),
+        }
+    }
+
+    // request the latest state of the server to be passed to reset
+    pub fn reload(&self) -> Box<Future<Item = State<O>, Error = ClientError>> {
+        Box::new(
+            self.connection
+                .get_latest_state()
+                .map_err(Into::into)
+                .map_err(ClientError::ConnectionError),
+        )
+    }
+
+    // replace the content with the state of the server, e.g. after a divergence
+    // the operations in flight must be acknowledged first, and the buffer is dropped
+    pub fn reset(&mut self, state: State<O>) -> Result<(), ClientError> {
+        if !self.in_flight.is_empty() {
+            return Err(ClientError::Syncing);
+        }
+
+        self.base_id = state.id;
+        self.base_content = state.content;
+        self.current_diff = None;
+        Ok(())
+    }
+
+    pub fn send_get_patch(&self) -> Box<Future<Item = Patch<O>, Error = ClientError>> {
+        use self::futures::future::err;
+
+        if !self.in_flight.is_empty() {
+            return Box::new(err(ClientError::Syncing));
+        }
+
+        Box::new(
+            self.connection
+                .get_patch_since(&self.base_id)
+                .map_err(Into::into)
+                .map_err(ClientError::ConnectionError),
+        )
+    }
+}
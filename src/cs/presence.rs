@@ -0,0 +1,214 @@
+// presence, e.g. cursors and selections of the users, shared apart from the document
+// presence changes much more often than the document and the latest one is all that matters,
+// so it is not recorded in the history of the server. instead, each presence is tied to the
+// state it was made for and transformed against the operations accepted after it
+
+use super::*;
+use super::super::Operation;
+use super::server::{Observer, Server};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+extern crate futures;
+use self::futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+
+// something which moves along with the document
+pub trait Transform<O>: Sized {
+    // None if it does not make sense any more, e.g. a selected range was deleted
+    fn transform(self, op: &O) -> Option<Self>;
+}
+
+impl<O, T: Transform<O>> Transform<O> for Vec<T> {
+    fn transform(self, op: &O) -> Option<Self> {
+        Some(self.into_iter().filter_map(|t| t.transform(op)).collect())
+    }
+}
+
+// the presence of a client on the state id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Presence<P> {
+    pub client_id: ClientId,
+    pub id: Id,
+    pub data: P,
+}
+
+impl<P> Presence<P> {
+    // move to the state the patch leads to
+    pub fn transform<O>(self, patch: &Patch<O>) -> Option<Self>
+    where
+        P: Transform<O>,
+    {
+        let Presence { client_id, data, .. } = self;
+        data.transform(&patch.diff).map(|data| Presence {
+            client_id: client_id,
+            id: patch.id.clone(),
+            data: data,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PresenceEvent<P> {
+    Update(Presence<P>),
+    Leave(ClientId),
+}
+
+// keeps the latest presence of each client along with a Server and broadcasts the changes
+pub struct PresenceHub<P> {
+    presences: HashMap<ClientId, Presence<P>>,
+    // the state each client last reported a presence on. the stored presences are moved on
+    // with the server, so the updates of a client are ordered by these instead
+    reported: HashMap<ClientId, Id>,
+    listeners: Vec<UnboundedSender<PresenceEvent<P>>>,
+}
+
+impl<P: Clone> PresenceHub<P> {
+    pub fn new() -> Self {
+        PresenceHub {
+            presences: HashMap::new(),
+            reported: HashMap::new(),
+            listeners: vec![],
+        }
+    }
+
+    // receive the changes of presence, starting with the current ones
+    pub fn subscribe(&mut self) -> UnboundedReceiver<PresenceEvent<P>> {
+        let (sender, receiver) = unbounded();
+        for presence in self.presences.values() {
+            let _ = sender.unbounded_send(PresenceEvent::Update(presence.clone()));
+        }
+        self.listeners.push(sender);
+        receiver
+    }
+
+    fn notify(&mut self, event: PresenceEvent<P>) {
+        self.listeners
+            .retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+    }
+
+    pub fn get(&self, client_id: &ClientId) -> Option<&Presence<P>> {
+        self.presences.get(client_id)
+    }
+
+    // replace the presence of a client
+    // it is transformed to the current state of the server before broadcasted
+    // a presence on an older state than the client reported before is ignored
+    pub fn update<O>(&mut self, server: &Server<O>, presence: Presence<P>) -> Result<(), String>
+    where
+        O: Operation,
+        P: Transform<O>,
+    {
+        if let Some(reported) = self.reported.get(&presence.client_id) {
+            if presence.id < *reported {
+                return Ok(());
+            }
+        }
+
+        let client_id = presence.client_id.clone();
+        let patch = server.get_patch(&presence.id)?;
+        self.reported.insert(client_id.clone(), presence.id.clone());
+        self.replace(client_id, presence.transform(&patch));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, client_id: &ClientId) {
+        self.reported.remove(client_id);
+        self.leave(client_id);
+    }
+
+    fn leave(&mut self, client_id: &ClientId) {
+        if self.presences.remove(client_id).is_some() {
+            self.notify(PresenceEvent::Leave(client_id.clone()));
+        }
+    }
+
+    // transform the presences to the current state of the server, e.g. after it accepted
+    // operations, and broadcast them
+    pub fn catch_up<O>(&mut self, server: &Server<O>) -> Result<(), String>
+    where
+        O: Operation,
+        P: Transform<O>,
+    {
+        let current = server.current_state().id.clone();
+        let outdated: Vec<_> = self.presences
+            .values()
+            .filter(|presence| presence.id < current)
+            .cloned()
+            .collect();
+        for presence in outdated {
+            let client_id = presence.client_id.clone();
+            let patch = server.get_patch(&presence.id)?;
+            self.replace(client_id, presence.transform(&patch));
+        }
+        Ok(())
+    }
+
+    // move the presences on the parent of a new state to it
+    fn follow<O>(&mut self, state: &State<O>)
+    where
+        O: Operation,
+        P: Transform<O>,
+    {
+        let outdated: Vec<_> = self.presences
+            .values()
+            .filter(|presence| presence.id == state.parent)
+            .cloned()
+            .collect();
+        for Presence { client_id, data, .. } in outdated {
+            let presence = data.transform(&state.diff).map(|data| Presence {
+                client_id: client_id.clone(),
+                id: state.id.clone(),
+                data: data,
+            });
+            self.replace(client_id, presence);
+        }
+    }
+
+    fn replace(&mut self, client_id: ClientId, presence: Option<Presence<P>>) {
+        match presence {
+            Some(presence) => {
+                self.presences.insert(client_id, presence.clone());
+                self.notify(PresenceEvent::Update(presence));
+            }
+            // the client may still report a presence which makes sense
+            None => self.leave(&client_id),
+        }
+    }
+}
+
+// a PresenceHub which follows a server without calling catch_up
+// added to the server as an observer, it transforms the presences whenever the server accepts
+// an operation. the hub must be caught up with the server when it is added
+pub struct SharedPresenceHub<P>(Arc<Mutex<PresenceHub<P>>>);
+
+impl<P> Clone for SharedPresenceHub<P> {
+    fn clone(&self) -> Self {
+        SharedPresenceHub(self.0.clone())
+    }
+}
+
+impl<P: Clone> SharedPresenceHub<P> {
+    pub fn new(hub: PresenceHub<P>) -> Self {
+        SharedPresenceHub(Arc::new(Mutex::new(hub)))
+    }
+
+    // fails if a thread panicked while holding the lock
+    pub fn lock(&self) -> Result<MutexGuard<PresenceHub<P>>, String> {
+        self.0
+            .lock()
+            .map_err(|_| "the presence hub was poisoned by a panicked thread".to_string())
+    }
+}
+
+impl<O, P> Observer<O> for SharedPresenceHub<P>
+where
+    O: Operation,
+    P: Transform<O> + Clone + Send,
+{
+    fn accepted(&mut self, state: &State<O>) {
+        if let Ok(mut hub) = self.lock() {
+            hub.follow(state);
+        }
+    }
+}
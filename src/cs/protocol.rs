@@ -0,0 +1,46 @@
+use super::*;
+use super::super::Operation;
+
+use serde::{Deserialize, Serialize};
+
+// messages exchanged between a client and a server over the network
+
+// sent from a client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Request<O> {
+    GetLatestState,
+    GetPatchSince(Id),
+    SendOperation(Submission<O>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "O: Serialize, O::Target: Serialize",
+              deserialize = "O: Deserialize<'de>, O::Target: Deserialize<'de>"))]
+pub enum Reply<O: Operation> {
+    State(State<O>),
+    Patch(Patch<O>),
+}
+
+// sent from the server
+// the server greets a new connection with Hello and pushes states at any time.
+// every request gets exactly one Reply, in the order of the requests
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "O: Serialize, O::Target: Serialize",
+              deserialize = "O: Deserialize<'de>, O::Target: Deserialize<'de>"))]
+pub enum Message<O: Operation> {
+    Hello(ClientId),
+    Push(State<O>),
+    Reply(Result<Reply<O>, String>),
+}
+
+// answer a request from the client
+pub fn dispatch<O: Operation>(
+    server: &mut server::Server<O>,
+    request: Request<O>,
+) -> Result<Reply<O>, String> {
+    match request {
+        Request::GetLatestState => Ok(Reply::State(server.current_state().clone())),
+        Request::GetPatchSince(id) => server.get_patch(&id).map(Reply::Patch),
+        Request::SendOperation(submission) => server.submit(submission).map(Reply::Patch),
+    }
+}
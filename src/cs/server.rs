@@ -3,6 +3,19 @@ use super::super::Operation;
 
 use serde::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// milliseconds since the unix epoch
+fn now() -> u64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos()) / 1_000_000
+}
+
 pub trait Connection<O: Operation> {
     fn send_state(&mut self, state: &State<O>);
 }
@@ -19,12 +32,117 @@ impl<'c, O: Operation, C: Connection<O> + ?Sized + 'c> Connection<O> for &'c mut
     }
 }
 
+// a random number identifying a document, without depending on rand
+fn new_nonce() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(now());
+    hasher.finish()
+}
+
+// where a fork branched off
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct ForkPoint {
+    // the nonce of the original document
+    parent: u64,
+    // the state of the original document the fork was made at
+    id: Id,
+}
+
+// checks an operation before the server accepts it, e.g. for size limits or permissions
+// operation is transformed to be applied to state, the current state of the server,
+// and metadata is what will be recorded in the new state
+pub trait Validator<O: Operation>: Send {
+    fn validate(
+        &self,
+        state: &State<O>,
+        operation: &O,
+        metadata: &Metadata,
+    ) -> Result<(), Rejection>;
+}
+
+impl<O, F> Validator<O> for F
+where
+    O: Operation,
+    F: Fn(&State<O>, &O, &Metadata) -> Result<(), Rejection> + Send,
+{
+    fn validate(
+        &self,
+        state: &State<O>,
+        operation: &O,
+        metadata: &Metadata,
+    ) -> Result<(), Rejection> {
+        self(state, operation, metadata)
+    }
+}
+
+// notified of each new state after the server accepted it, e.g. to follow the content
+pub trait Observer<O: Operation>: Send {
+    fn accepted(&mut self, state: &State<O>);
+}
+
+impl<O, F> Observer<O> for F
+where
+    O: Operation,
+    F: FnMut(&State<O>) + Send,
+{
+    fn accepted(&mut self, state: &State<O>) {
+        self(state)
+    }
+}
+
+// patches carry the metadata of at most this many states unless the server is told otherwise
+pub const DEFAULT_PATCH_METADATA_LIMIT: usize = 64;
+
+fn default_patch_metadata_limit() -> usize {
+    DEFAULT_PATCH_METADATA_LIMIT
+}
+
+// the response to a submission
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Acknowledgement<O> {
+    seq: usize,
+    // the operation as the client holds it while waiting for this acknowledgement
+    sent: O,
+    patch: Patch<O>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Session<O> {
+    // the user behind the client, recorded in the metadata of the states it makes
+    #[serde(default)]
+    author: Option<String>,
+    // the last applied seq
+    seq: usize,
+    // acknowledgements the client may not have received yet, in the order of seq
+    acknowledgements: VecDeque<Acknowledgement<O>>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Server<O: Operation> {
     #[serde(bound(serialize = "O: Serialize, O::Target: Serialize",
                   deserialize = "O: Deserialize<'de>, O::Target: Deserialize<'de>"))]
     history: Vec<State<O>>,
     //connections: Vec<Box<Connection>>,
+    #[serde(default)]
+    next_client_id: usize,
+    #[serde(default)]
+    sessions: HashMap<ClientId, Session<O>>,
+    // identifies the document among its forks
+    #[serde(default = "new_nonce")]
+    nonce: u64,
+    #[serde(default)]
+    fork_point: Option<ForkPoint>,
+    // the nonces of the forks merged into this document, which can not be merged again
+    #[serde(default)]
+    merged: HashSet<u64>,
+    // the number of the newest states whose metadata a patch carries
+    #[serde(default = "default_patch_metadata_limit")]
+    patch_metadata_limit: usize,
+    // run in the order they were added, the first rejection wins
+    #[serde(skip)]
+    validators: Vec<Box<Validator<O>>>,
+    #[serde(skip)]
+    observers: Vec<Box<Observer<O>>>,
 }
 
 impl<O: Operation> Server<O> {
@@ -35,54 +153,377 @@ impl<O: Operation> Server<O> {
                 id: Id(0),
                 diff: O::default(),
                 content: O::Target::default(),
+                checksum: O::checksum(&O::Target::default()),
+                metadata: Metadata::default(),
             },
         ];
         Server {
             history: history,
             //connections: vec![]
+            next_client_id: 0,
+            sessions: HashMap::new(),
+            nonce: new_nonce(),
+            fork_point: None,
+            merged: HashSet::new(),
+            patch_metadata_limit: DEFAULT_PATCH_METADATA_LIMIT,
+            validators: vec![],
+            observers: vec![],
         }
     }
 
-    pub fn get_patch(&self, since_id: &Id) -> Result<(Id, O), String> {
-        if self.history.len() <= since_id.0 {
-            Err("index out of range".into())
-        } else {
-            let parent_id = Id(self.history.len() - 1);
-            let mut op = O::nop(&self.history[since_id.0].content);
+    // a new document sharing the history up to the state id, which evolves independently
+    // the states after id are made in the fork only. sessions, validators and observers are not
+    // inherited
+    pub fn fork(&self, id: &Id) -> Result<Self, String> {
+        self.state_at(id)?;
+        Ok(Server {
+            history: self.history[..id.0 + 1].to_vec(),
+            next_client_id: 0,
+            sessions: HashMap::new(),
+            nonce: new_nonce(),
+            fork_point: Some(ForkPoint {
+                parent: self.nonce,
+                id: id.clone(),
+            }),
+            merged: HashSet::new(),
+            patch_metadata_limit: self.patch_metadata_limit,
+            validators: vec![],
+            observers: vec![],
+        })
+    }
+
+    pub fn fork_point(&self) -> Option<&Id> {
+        self.fork_point.as_ref().map(|fork_point| &fork_point.id)
+    }
+
+    // apply the changes made in the fork since it was forked
+    // they are transformed against the changes made here since the fork point.
+    // only forks made from this document can be merged, and each of them only once
+    pub fn merge(&mut self, fork: Self, metadata: Metadata) -> Result<Patch<O>, String> {
+        let fork_point = fork.fork_point.clone().ok_or("not a fork")?;
+        if fork_point.parent != self.nonce
+            || self.state_at(&fork_point.id)?.checksum != fork.state_at(&fork_point.id)?.checksum
+        {
+            return Err("the fork is not of this document".into());
+        }
+        if self.merged.contains(&fork.nonce) {
+            return Err("the fork is already merged".into());
+        }
+        let diff = fork.get_patch(&fork_point.id)?.diff;
+        let patch = self.modify_with(metadata, fork_point.id, diff)?;
+        self.merged.insert(fork.nonce);
+        Ok(patch)
+    }
+
+    pub fn add_validator<V: Validator<O> + 'static>(&mut self, validator: V) {
+        self.validators.push(Box::new(validator));
+    }
+
+    pub fn add_observer<T: Observer<O> + 'static>(&mut self, observer: T) {
+        self.observers.push(Box::new(observer));
+    }
+
+    // 0 leaves the metadata out of patches. the rest can be read with state_at
+    pub fn set_patch_metadata_limit(&mut self, limit: usize) {
+        self.patch_metadata_limit = limit;
+    }
+
+    // the metadata of the newest states within the limit, oldest first
+    fn recent_metadata(&self, states: &[State<O>]) -> Vec<Metadata> {
+        let start = states.len().saturating_sub(self.patch_metadata_limit);
+        states[start..]
+            .iter()
+            .map(|state| state.metadata.clone())
+            .collect()
+    }
+
+    pub fn new_client_id(&mut self) -> ClientId {
+        let id = ClientId(self.next_client_id);
+        self.next_client_id += 1;
+        id
+    }
+
+    fn session(&mut self, client_id: ClientId) -> &mut Session<O> {
+        self.sessions.entry(client_id).or_insert_with(|| Session {
+            author: None,
+            seq: 0,
+            acknowledgements: VecDeque::new(),
+        })
+    }
 
-            for state in self.history.iter().skip(since_id.0 + 1) {
+    // the user behind the client, e.g. after it is authenticated
+    pub fn set_author(&mut self, client_id: ClientId, author: String) {
+        self.session(client_id).author = Some(author);
+    }
+
+    pub fn get_patch(&self, since_id: &Id) -> Result<Patch<O>, String> {
+        let latest_id = self.current_state().id.clone();
+        self.patch_between(since_id, &latest_id)
+    }
+
+    pub fn state_at(&self, id: &Id) -> Result<&State<O>, String> {
+        self.history
+            .get(id.0)
+            .ok_or_else(|| "index out of range".into())
+    }
+
+    // the patch from the state from to the state to
+    // going back to an older state undoes the states after it, which requires the operation
+    // to support inversion. the metadata are those of the undone states then, newest first.
+    // either way, the metadata of the newest states within the limit are included
+    pub fn patch_between(&self, from: &Id, to: &Id) -> Result<Patch<O>, String> {
+        let target = self.state_at(to)?;
+        let mut op = O::nop(&self.state_at(from)?.content);
+        let metadata;
+
+        if from <= to {
+            let states = &self.history[from.0 + 1..to.0 + 1];
+            for state in states.iter() {
                 op = op.compose(state.diff.clone());
             }
-
-            Ok((parent_id, op))
+            metadata = self.recent_metadata(states);
+        } else {
+            let states = &self.history[to.0 + 1..from.0 + 1];
+            for state in states.iter().rev() {
+                // the diff of a state applies to the previous state
+                let source = &self.history[state.id.0 - 1].content;
+                let inverted = state
+                    .diff
+                    .invert(source)
+                    .ok_or("the operation does not support inversion")?;
+                op = op.compose(inverted);
+            }
+            let mut recent = self.recent_metadata(states);
+            recent.reverse();
+            metadata = recent;
         }
+
+        Ok(Patch {
+            id: to.clone(),
+            diff: op,
+            checksum: target.checksum,
+            metadata: metadata,
+            rejection: None,
+            from_parent: false,
+        })
     }
 
     pub fn current_state(&self) -> &State<O> {
         self.history.last().unwrap()
     }
 
+    // the states after since_id, oldest first
+    pub fn states_since(&self, since_id: &Id) -> &[State<O>] {
+        let start = (since_id.0 + 1).min(self.history.len());
+        &self.history[start..]
+    }
+
     pub fn connect<'a>(&'a mut self, mut connection: Box<Connection<O> + 'a>) {
         connection.send_state(self.current_state());
         //self.connections.push(connection);
     }
 
-    pub fn modify(&mut self, parent: Id, operation: O) -> Result<(Id, O), String> {
-        let (parent_id, server_op) = self.get_patch(&parent)?;
+    pub fn modify(&mut self, parent: Id, operation: O) -> Result<Patch<O>, String> {
+        self.modify_with(Metadata::default(), parent, operation)
+    }
+
+    // modify with metadata recorded in the new state
+    // the timestamp is set by the server, and the author is taken from the session if missing
+    // an operation rejected by a validator is an error
+    pub fn modify_with(
+        &mut self,
+        metadata: Metadata,
+        parent: Id,
+        operation: O,
+    ) -> Result<Patch<O>, String> {
+        let patch = self.accept(metadata, parent, operation)?;
+        match patch.rejection {
+            Some(rejection) => Err(rejection.to_string()),
+            None => Ok(patch),
+        }
+    }
 
-        let (server_diff, client_diff) = operation.transform(server_op.clone());
+    fn validate(&self, operation: &O, metadata: &Metadata) -> Result<(), Rejection> {
+        let state = self.current_state();
+        for validator in self.validators.iter() {
+            validator.validate(state, operation, metadata)?;
+        }
+        Ok(())
+    }
+
+    // modify unless a validator rejects the operation
+    // the patch for a rejected operation undoes it and brings the client to the current state,
+    // so that the client can apply it in the same way as an accepted one. an operation which
+    // can not be inverted gets a patch from its parent instead
+    fn accept(
+        &mut self,
+        mut metadata: Metadata,
+        parent: Id,
+        operation: O,
+    ) -> Result<Patch<O>, String> {
+        let Patch {
+            id: parent_id,
+            diff: server_op,
+            ..
+        } = self.get_patch(&parent)?;
+
+        let (server_diff, client_diff) = operation.clone().transform(server_op.clone());
         let content_source = self.history[parent.0].content.clone();
 
+        metadata.timestamp = now();
+        if metadata.author.is_none() {
+            if let Some(ref session) = metadata.session {
+                metadata.author = self.sessions
+                    .get(session)
+                    .and_then(|session| session.author.clone());
+            }
+        }
+
+        if let Err(rejection) = self.validate(&server_diff, &metadata) {
+            let (diff, from_parent) = match operation.invert(&content_source) {
+                Some(undo) => (undo.compose(server_op), false),
+                None => (server_op, true),
+            };
+            let latest = self.current_state();
+            return Ok(Patch {
+                id: latest.id.clone(),
+                diff: diff,
+                checksum: latest.checksum,
+                metadata: self.recent_metadata(self.states_since(&parent)),
+                rejection: Some(rejection),
+                from_parent: from_parent,
+            });
+        }
+
         let id = Id(self.history.len());
+        let content = server_op
+            .compose(server_diff.clone())
+            .apply(&content_source);
+        let checksum = O::checksum(&content);
         self.history.push(State {
             parent: parent_id.clone(),
             id: id.clone(),
-            content: server_op
-                .compose(server_diff.clone())
-                .apply(&content_source),
+            content: content,
             diff: server_diff,
+            checksum: checksum,
+            metadata: metadata,
+        });
+        let state = self.history.last().unwrap();
+        for observer in self.observers.iter_mut() {
+            observer.accepted(state);
+        }
+
+        Ok(Patch {
+            id: id,
+            diff: client_diff,
+            checksum: checksum,
+            metadata: self.recent_metadata(self.states_since(&parent)),
+            rejection: None,
+            from_parent: false,
+        })
+    }
+
+    // modify with deduplication
+    // a submission whose seq was already applied for the client gets the original
+    // acknowledgement back. seq must increase one by one.
+    // a submission based on an older state than the acknowledgement of the previous one is
+    // considered to be built on top of the operations in flight (pipelining), so it is
+    // transformed against the acknowledgements the client has not received yet, in the same
+    // way as the client does when it receives them
+    // a rejected submission is acknowledged as well, with a patch undoing it. one which can
+    // not be undone can not have others built on it, so those are errors without using up
+    // their seq
+    pub fn submit(&mut self, submission: Submission<O>) -> Result<Patch<O>, String> {
+        let Submission {
+            client_id,
+            seq,
+            parent,
+            diff,
+            acknowledged,
+        } = submission;
+
+        let (base_id, rebased) = {
+            let session = self.session(client_id.clone());
+
+            if let Some(ack) = session.acknowledgements.iter().find(|ack| ack.seq == seq) {
+                return Ok(ack.patch.clone());
+            }
+            if seq <= session.seq {
+                return Err(format!(
+                    "stale submission: seq {} is older than {}",
+                    seq, session.seq
+                ));
+            } else if seq != session.seq + 1 {
+                return Err(format!(
+                    "submission out of order: seq {} after {}",
+                    seq, session.seq
+                ));
+            }
+
+            // the client has received acknowledgements included in the parent
+            // the operations still in flight were transformed against them
+            while session
+                .acknowledgements
+                .front()
+                .map_or(false, |ack| match acknowledged {
+                    Some(acknowledged) => ack.seq <= acknowledged,
+                    None => ack.patch.id <= parent,
+                }) {
+                let ack = session.acknowledgements.pop_front().unwrap();
+                let mut patch = ack.patch.diff;
+                for later in session.acknowledgements.iter_mut() {
+                    let (sent, rest) = later.sent.clone().transform(patch);
+                    later.sent = sent;
+                    patch = rest;
+                }
+            }
+
+            if session
+                .acknowledgements
+                .iter()
+                .any(|ack| ack.patch.from_parent)
+            {
+                return Err("the submission is built on a rejected one".into());
+            }
+
+            // the rest are built on top of each other, and so is the submission
+            let mut sent: Vec<O> = session
+                .acknowledgements
+                .iter()
+                .map(|ack| ack.sent.clone())
+                .collect();
+            let mut rebased = diff.clone();
+            for (i, ack) in session.acknowledgements.iter().enumerate() {
+                let mut patch = ack.patch.diff.clone();
+                for later in sent[i + 1..].iter_mut() {
+                    let (transformed, rest) = later.clone().transform(patch);
+                    *later = transformed;
+                    patch = rest;
+                }
+                rebased = rebased.transform(patch).0;
+            }
+
+            let base_id = session
+                .acknowledgements
+                .back()
+                .map_or(parent, |ack| ack.patch.id.clone());
+            (base_id, rebased)
+        };
+
+        let metadata = Metadata {
+            session: Some(client_id.clone()),
+            ..Metadata::default()
+        };
+        let patch = self.accept(metadata, base_id, rebased)?;
+
+        let session = self.sessions.get_mut(&client_id).unwrap();
+        session.seq = seq;
+        session.acknowledgements.push_back(Acknowledgement {
+            seq: seq,
+            sent: diff,
+            patch: patch.clone(),
         });
 
-        Ok((id, client_diff))
+        Ok(patch)
     }
 }
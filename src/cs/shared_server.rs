@@ -0,0 +1,115 @@
+use Operation;
+use super::{ClientId, Id, Patch, State, Submission};
+use super::server::Server;
+use super::client;
+
+use futures::Future;
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// a server shared between threads
+// every request locks the server for its duration, so requests are applied one at a time
+pub struct SharedServer<O: Operation>(Arc<Mutex<Server<O>>>);
+
+impl<O: Operation> Clone for SharedServer<O> {
+    fn clone(&self) -> Self {
+        SharedServer(self.0.clone())
+    }
+}
+
+impl<O: Operation> SharedServer<O> {
+    pub fn new(server: Server<O>) -> Self {
+        SharedServer(Arc::new(Mutex::new(server)))
+    }
+
+    // the server as its mutex, e.g. for code locking it in the same way as before SharedServer
+    pub fn mutex(&self) -> Arc<Mutex<Server<O>>> {
+        self.0.clone()
+    }
+
+    // lock the server to call it directly
+    // fails if a thread panicked while holding the lock, since the server may be broken then
+    pub fn lock(&self) -> Result<MutexGuard<Server<O>>, String> {
+        self.0
+            .lock()
+            .map_err(|_| "the server was poisoned by a panicked thread".to_string())
+    }
+
+    pub fn current_state(&self) -> Result<State<O>, String> {
+        Ok(self.lock()?.current_state().clone())
+    }
+
+    pub fn get_patch(&self, since_id: &Id) -> Result<Patch<O>, String> {
+        self.lock()?.get_patch(since_id)
+    }
+
+    pub fn modify(&self, parent: Id, operation: O) -> Result<Patch<O>, String> {
+        self.lock()?.modify(parent, operation)
+    }
+
+    pub fn submit(&self, submission: Submission<O>) -> Result<Patch<O>, String> {
+        self.lock()?.submit(submission)
+    }
+
+    // a connection for a new client, which can be moved to another thread
+    pub fn connect(&self) -> Result<SharedConnection<O>, String> {
+        let client_id = self.lock()?.new_client_id();
+        Ok(SharedConnection {
+            server: self.clone(),
+            client_id: client_id,
+        })
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "error: {}", _0)]
+pub struct SharedConnectionError(String);
+
+impl From<String> for SharedConnectionError {
+    fn from(s: String) -> Self {
+        SharedConnectionError(s)
+    }
+}
+
+pub struct SharedConnection<O: Operation> {
+    server: SharedServer<O>,
+    client_id: ClientId,
+}
+
+impl<O: Operation> SharedConnection<O> {
+    pub fn server(&self) -> &SharedServer<O> {
+        &self.server
+    }
+}
+
+impl<O> client::Connection<O> for SharedConnection<O>
+where
+    O: Operation + Send + 'static,
+    O::Target: Send,
+{
+    type Error = SharedConnectionError;
+    type Output = Box<Future<Item = Patch<O>, Error = Self::Error> + Send>;
+    type StateFuture = Box<Future<Item = State<O>, Error = Self::Error> + Send>;
+
+    fn client_id(&self) -> ClientId {
+        self.client_id.clone()
+    }
+
+    fn get_latest_state(&self) -> Self::StateFuture {
+        use futures::future::result;
+
+        Box::new(result(self.server.current_state().map_err(Into::into)))
+    }
+
+    fn get_patch_since(&self, since_id: &Id) -> Self::Output {
+        use futures::future::result;
+
+        Box::new(result(self.server.get_patch(since_id).map_err(Into::into)))
+    }
+
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        use futures::future::result;
+
+        Box::new(result(self.server.submit(submission).map_err(Into::into)))
+    }
+}
@@ -0,0 +1,502 @@
+use super::*;
+use super::super::Operation;
+use super::client::{Client, ClientError, Connection};
+use super::protocol::{dispatch, Reply, Request};
+use super::server::Server;
+use super::server;
+
+extern crate rand;
+use self::rand::{Rng, SeedableRng, XorShiftRng};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::mem::replace;
+use std::rc::Rc;
+
+extern crate futures;
+use self::futures::{Async, Future, FutureExt, Poll};
+use self::futures::executor::block_on;
+use self::futures::task::Context;
+
+#[derive(Debug, Fail)]
+pub enum SimulationError {
+    // the request or the reply was dropped, or the client was partitioned.
+    // a real client would notice it by a timeout
+    #[fail(display = "Message lost")]
+    Lost,
+    #[fail(display = "Server error: {}", _0)]
+    Server(String),
+    #[fail(display = "Unexpected reply from the server")]
+    UnexpectedReply,
+}
+
+enum Slot<O: Operation> {
+    Waiting,
+    Ready(Result<Reply<O>, String>),
+    Lost,
+}
+
+type SlotRef<O> = Rc<RefCell<Slot<O>>>;
+
+enum Payload<O: Operation> {
+    Request(Request<O>),
+    Reply(Result<Reply<O>, String>),
+    Push(State<O>),
+}
+
+impl<O: Operation> Clone for Payload<O> {
+    fn clone(&self) -> Self {
+        match *self {
+            Payload::Request(ref request) => Payload::Request(request.clone()),
+            Payload::Reply(ref reply) => Payload::Reply(reply.clone()),
+            Payload::Push(ref state) => Payload::Push(state.clone()),
+        }
+    }
+}
+
+struct Packet<O: Operation> {
+    client_id: ClientId,
+    payload: Payload<O>,
+    // where the reply goes. copies of a packet have none, so their replies are discarded
+    slot: Option<SlotRef<O>>,
+}
+
+struct Queue<O: Operation> {
+    packets: Vec<Packet<O>>,
+    rng: XorShiftRng,
+    drop_rate: f64,
+    duplicate_rate: f64,
+}
+
+impl<O: Operation> Queue<O> {
+    fn send(&mut self, packet: Packet<O>) {
+        if self.rng.gen::<f64>() < self.drop_rate {
+            Self::lose(packet);
+            return;
+        }
+        if self.rng.gen::<f64>() < self.duplicate_rate {
+            self.packets.push(Self::copy(&packet));
+        }
+        self.packets.push(packet);
+    }
+
+    fn copy(packet: &Packet<O>) -> Packet<O> {
+        Packet {
+            client_id: packet.client_id.clone(),
+            payload: packet.payload.clone(),
+            slot: None,
+        }
+    }
+
+    fn lose(packet: Packet<O>) {
+        if let Some(slot) = packet.slot {
+            *slot.borrow_mut() = Slot::Lost;
+        }
+    }
+}
+
+// delivers the states pushed by the server
+struct Peer<'a, O: Operation + 'a> {
+    queue: &'a mut Queue<O>,
+    client_id: ClientId,
+}
+
+impl<'a, O: Operation + 'a> server::Connection<O> for Peer<'a, O> {
+    fn send_state(&mut self, state: &State<O>) {
+        self.queue.send(Packet {
+            client_id: self.client_id.clone(),
+            payload: Payload::Push(state.clone()),
+            slot: None,
+        });
+    }
+}
+
+struct NetworkState<O: Operation> {
+    server: Server<O>,
+    queue: Queue<O>,
+    partitioned: HashSet<ClientId>,
+    // the last state pushed to each client
+    pushed: HashMap<ClientId, State<O>>,
+}
+
+// a simulated network between a server and clients, driven by a seeded random number generator
+// messages are queued until they are delivered in any order, either explicitly or by waiting
+// for a reply. they may be dropped or duplicated at random
+#[derive(Clone)]
+pub struct Network<O: Operation>(Rc<RefCell<NetworkState<O>>>);
+
+impl<O: Operation + 'static> Network<O> {
+    pub fn new(seed: u32) -> Self {
+        Network(Rc::new(RefCell::new(NetworkState {
+            server: Server::new(),
+            queue: Queue {
+                packets: vec![],
+                rng: XorShiftRng::from_seed([0x193a_6754, 0xa8a7_d469, 0x9783_0e05, seed]),
+                drop_rate: 0.0,
+                duplicate_rate: 0.0,
+            },
+            partitioned: HashSet::new(),
+            pushed: HashMap::new(),
+        })))
+    }
+
+    pub fn set_drop_rate(&self, rate: f64) {
+        self.0.borrow_mut().queue.drop_rate = rate;
+    }
+
+    pub fn set_duplicate_rate(&self, rate: f64) {
+        self.0.borrow_mut().queue.duplicate_rate = rate;
+    }
+
+    // open a connection for a new client
+    // the server pushes its state through the network
+    pub fn connect(&self) -> SimulatedConnection<O> {
+        let mut guard = self.0.borrow_mut();
+        let state = &mut *guard;
+
+        let client_id = state.server.new_client_id();
+        state.server.connect(Box::new(Peer {
+            queue: &mut state.queue,
+            client_id: client_id.clone(),
+        }));
+
+        SimulatedConnection {
+            network: self.clone(),
+            client_id: client_id,
+        }
+    }
+
+    pub fn with_server<T, F: FnOnce(&mut Server<O>) -> T>(&self, f: F) -> T {
+        f(&mut self.0.borrow_mut().server)
+    }
+
+    // the number of messages in flight
+    pub fn pending(&self) -> usize {
+        self.0.borrow().queue.packets.len()
+    }
+
+    // deliver a random message
+    // returns false if no message is in flight
+    pub fn step(&self) -> bool {
+        let len = self.pending();
+        if len == 0 {
+            return false;
+        }
+
+        let index = self.0.borrow_mut().queue.rng.gen_range(0, len);
+        self.deliver(index);
+        true
+    }
+
+    // deliver the index-th message in flight, counted in the order of sending
+    pub fn deliver(&self, index: usize) {
+        let mut guard = self.0.borrow_mut();
+        let state = &mut *guard;
+
+        let packet = state.queue.packets.remove(index);
+        if state.partitioned.contains(&packet.client_id) {
+            Queue::lose(packet);
+            return;
+        }
+
+        let Packet {
+            client_id,
+            payload,
+            slot,
+        } = packet;
+        match payload {
+            Payload::Request(request) => {
+                let reply = dispatch(&mut state.server, request);
+                state.queue.send(Packet {
+                    client_id: client_id,
+                    payload: Payload::Reply(reply),
+                    slot: slot,
+                });
+            }
+            Payload::Reply(reply) => if let Some(slot) = slot {
+                *slot.borrow_mut() = Slot::Ready(reply);
+            },
+            Payload::Push(pushed) => {
+                state.pushed.insert(client_id, pushed);
+            }
+        }
+    }
+
+    // drop the index-th message in flight
+    pub fn lose(&self, index: usize) {
+        let packet = self.0.borrow_mut().queue.packets.remove(index);
+        Queue::lose(packet);
+    }
+
+    // send the index-th message in flight again
+    // the reply to the copy is discarded
+    pub fn duplicate(&self, index: usize) {
+        let mut state = self.0.borrow_mut();
+        let packet = Queue::copy(&state.queue.packets[index]);
+        state.queue.packets.push(packet);
+    }
+
+    // messages from and to a partitioned client are lost when they are delivered
+    pub fn partition(&self, client_id: &ClientId) {
+        self.0.borrow_mut().partitioned.insert(client_id.clone());
+    }
+
+    pub fn heal(&self, client_id: &ClientId) {
+        self.0.borrow_mut().partitioned.remove(client_id);
+    }
+
+    pub fn is_partitioned(&self, client_id: &ClientId) -> bool {
+        self.0.borrow().partitioned.contains(client_id)
+    }
+
+    fn heal_all(&self) {
+        self.0.borrow_mut().partitioned.clear();
+    }
+
+    fn rng(&self) -> XorShiftRng {
+        self.0.borrow_mut().queue.rng.gen()
+    }
+}
+
+// the reply to a request on a simulated network
+// polling it delivers random messages until the reply arrives or turns out to be lost
+pub struct SimulatedFuture<O: Operation> {
+    network: Network<O>,
+    slot: SlotRef<O>,
+}
+
+impl<O: Operation + 'static> Future for SimulatedFuture<O> {
+    type Item = Reply<O>;
+    type Error = SimulationError;
+
+    fn poll(&mut self, _cx: &mut Context) -> Poll<Self::Item, Self::Error> {
+        loop {
+            match replace(&mut *self.slot.borrow_mut(), Slot::Waiting) {
+                Slot::Ready(Ok(reply)) => return Ok(Async::Ready(reply)),
+                Slot::Ready(Err(s)) => return Err(SimulationError::Server(s)),
+                Slot::Lost => return Err(SimulationError::Lost),
+                Slot::Waiting => {}
+            }
+
+            if !self.network.step() {
+                return Err(SimulationError::Lost);
+            }
+        }
+    }
+}
+
+pub struct SimulatedConnection<O: Operation> {
+    network: Network<O>,
+    client_id: ClientId,
+}
+
+impl<O: Operation + 'static> SimulatedConnection<O> {
+    pub fn pushed_state(&self) -> Option<State<O>> {
+        self.network.0.borrow().pushed.get(&self.client_id).cloned()
+    }
+
+    fn request(&self, request: Request<O>) -> SimulatedFuture<O> {
+        let slot = Rc::new(RefCell::new(Slot::Waiting));
+        self.network.0.borrow_mut().queue.send(Packet {
+            client_id: self.client_id.clone(),
+            payload: Payload::Request(request),
+            slot: Some(slot.clone()),
+        });
+
+        SimulatedFuture {
+            network: self.network.clone(),
+            slot: slot,
+        }
+    }
+
+    fn request_patch(
+        &self,
+        request: Request<O>,
+    ) -> Box<Future<Item = Patch<O>, Error = SimulationError>> {
+        Box::new(self.request(request).and_then(|reply| match reply {
+            Reply::Patch(patch) => Ok(patch),
+            _ => Err(SimulationError::UnexpectedReply),
+        }))
+    }
+}
+
+impl<O: Operation + 'static> Connection<O> for SimulatedConnection<O> {
+    type Error = SimulationError;
+    type Output = Box<Future<Item = Patch<O>, Error = Self::Error>>;
+    type StateFuture = Box<Future<Item = State<O>, Error = Self::Error>>;
+
+    fn client_id(&self) -> ClientId {
+        self.client_id.clone()
+    }
+
+    fn get_latest_state(&self) -> Self::StateFuture {
+        Box::new(
+            self.request(Request::GetLatestState)
+                .and_then(|reply| match reply {
+                    Reply::State(state) => Ok(state),
+                    _ => Err(SimulationError::UnexpectedReply),
+                }),
+        )
+    }
+
+    fn get_patch_since(&self, since_id: &Id) -> Self::Output {
+        self.request_patch(Request::GetPatchSince(since_id.clone()))
+    }
+
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        self.request_patch(Request::SendOperation(submission))
+    }
+}
+
+type SimulatedClient<O> = Client<O, SimulatedConnection<O>>;
+type PatchFuture<O> = Box<Future<Item = Patch<O>, Error = ClientError>>;
+
+// send the buffer, or resync
+fn start_request<O: Operation + 'static>(client: &mut SimulatedClient<O>) -> PatchFuture<O> {
+    if let Client::Buffering {
+        current_diff: Some(_),
+        ..
+    } = *client
+    {
+        if let Ok(future) = client.send_to_server() {
+            return Box::new(
+                future
+                    .map_err(Into::into)
+                    .map_err(ClientError::ConnectionError),
+            );
+        }
+    }
+    client.resync()
+}
+
+// lost messages are fine, since the client resyncs with the next request
+fn finish_request<O: Operation + 'static>(
+    client: &mut SimulatedClient<O>,
+    request: PatchFuture<O>,
+) -> Result<(), String> {
+    match block_on(request) {
+        Ok(patch) => client.apply_patch(patch).map_err(|e| e.to_string()),
+        Err(ClientError::ConnectionError(ref e))
+            if e.downcast_ref::<SimulationError>()
+                .map_or(false, |e| match *e {
+                    SimulationError::Lost => true,
+                    _ => false,
+                }) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_synced<O: Operation + 'static>(client: &SimulatedClient<O>) -> bool {
+    match *client {
+        Client::Buffering {
+            current_diff: None,
+            ..
+        } => true,
+        _ => false,
+    }
+}
+
+// how many times check_convergence tries to connect each client on average
+const MAX_CONNECT_ATTEMPTS: usize = 100;
+
+// run a random edit session of clients on the network, where generate makes a random
+// operation for the given content. then the network is healed and stops dropping and
+// duplicating messages, and the clients sync until quiescence.
+// returns the final content, or a description of how the clients failed to converge
+pub fn check_convergence<O, F>(
+    network: &Network<O>,
+    clients: usize,
+    steps: usize,
+    mut generate: F,
+) -> Result<O::Target, String>
+where
+    O: Operation + 'static,
+    O::Target: PartialEq + Debug,
+    F: FnMut(&mut XorShiftRng, &O::Target) -> O,
+{
+    let mut rng = network.rng();
+
+    let mut ids = vec![];
+    let mut sessions = vec![];
+    let mut attempts = 0;
+    while sessions.len() < clients {
+        // retry if the initial state is lost, unless the network loses everything
+        if attempts == MAX_CONNECT_ATTEMPTS * clients {
+            return Err(format!(
+                "only {} of {} clients could connect",
+                sessions.len(),
+                clients
+            ));
+        }
+        attempts += 1;
+
+        let connection = network.connect();
+        let id = connection.client_id();
+        if let Ok(client) = block_on(Client::with_connection(connection)) {
+            ids.push(id);
+            sessions.push((client, None));
+        }
+    }
+
+    for _ in 0..steps {
+        let i = rng.gen_range(0, clients);
+        let (ref mut client, ref mut request) = sessions[i];
+
+        match rng.gen_range(0, 10) {
+            0 | 1 | 2 => {
+                let content = client.unsynced_content()?;
+                client.push_operation(generate(&mut rng, &content));
+            }
+            3 | 4 => if request.is_none() {
+                *request = Some(start_request(client));
+            },
+            5 | 6 => if let Some(request) = request.take() {
+                finish_request(client, request)?;
+            },
+            7 | 8 => {
+                network.step();
+            }
+            _ => if network.is_partitioned(&ids[i]) {
+                network.heal(&ids[i]);
+            } else {
+                network.partition(&ids[i]);
+            },
+        }
+    }
+
+    network.heal_all();
+    network.set_drop_rate(0.0);
+    network.set_duplicate_rate(0.0);
+    while network.step() {}
+
+    for &mut (ref mut client, ref mut request) in sessions.iter_mut() {
+        if let Some(request) = request.take() {
+            finish_request(client, request)?;
+        }
+        while !is_synced(client) {
+            let request = start_request(client);
+            finish_request(client, request)?;
+        }
+    }
+    for &mut (ref mut client, _) in sessions.iter_mut() {
+        let request = client.send_get_patch();
+        finish_request(client, request)?;
+    }
+
+    let content = network.with_server(|server| server.current_state().content.clone());
+    for (i, &(ref client, _)) in sessions.iter().enumerate() {
+        let unsynced = client.unsynced_content()?;
+        if client.current_content()? != content || unsynced != content {
+            return Err(format!(
+                "client {} diverged: {:?} != {:?}",
+                i, unsynced, content
+            ));
+        }
+    }
+
+    Ok(content)
+}
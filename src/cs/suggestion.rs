@@ -0,0 +1,129 @@
+// suggestions, i.e. edits proposed by users which are reviewed before they get into the document
+// a suggestion is kept apart from the history of the server, tied to the state it was made on,
+// and transformed against the operations accepted after it. accepting a suggestion modifies the
+// server as if its author sent it
+
+use super::*;
+use super::super::Operation;
+use super::server::Server;
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize, Debug)]
+pub struct SuggestionId(pub usize);
+
+// a suggestion of a user on the state parent
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Suggestion<O> {
+    pub author: ClientId,
+    pub parent: Id,
+    pub diff: O,
+}
+
+impl<O: Operation> Suggestion<O> {
+    // move to the state the patch leads to
+    // the suggestion goes first when it conflicts with the patch, as in Server::modify
+    pub fn transform(self, patch: &Patch<O>) -> Self {
+        let Suggestion { author, diff, .. } = self;
+        let (diff, _) = diff.transform(patch.diff.clone());
+        Suggestion {
+            author: author,
+            parent: patch.id.clone(),
+            diff: diff,
+        }
+    }
+}
+
+// pending suggestions on a Server
+#[derive(Serialize, Deserialize)]
+pub struct Suggestions<O> {
+    suggestions: BTreeMap<SuggestionId, Suggestion<O>>,
+    next_id: usize,
+}
+
+impl<O: Operation> Suggestions<O> {
+    pub fn new() -> Self {
+        Suggestions {
+            suggestions: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    // record an operation on the state parent as a suggestion instead of applying it
+    pub fn suggest(
+        &mut self,
+        server: &Server<O>,
+        author: ClientId,
+        parent: Id,
+        diff: O,
+    ) -> Result<SuggestionId, String> {
+        let patch = server.get_patch(&parent)?;
+        let suggestion = Suggestion {
+            author: author,
+            parent: parent,
+            diff: diff,
+        }.transform(&patch);
+
+        let id = SuggestionId(self.next_id);
+        self.next_id += 1;
+        self.suggestions.insert(id.clone(), suggestion);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &SuggestionId) -> Option<&Suggestion<O>> {
+        self.suggestions.get(id)
+    }
+
+    pub fn iter(&self) -> Iter<SuggestionId, Suggestion<O>> {
+        self.suggestions.iter()
+    }
+
+    // transform the suggestions to the current state of the server, e.g. after it accepted
+    // operations
+    // fails without changing them if the server does not have their parents, e.g. when it is
+    // not the one they were made on
+    pub fn catch_up(&mut self, server: &Server<O>) -> Result<(), String> {
+        let current = server.current_state().id.clone();
+        let mut caught_up = BTreeMap::new();
+        for (id, suggestion) in self.suggestions.iter() {
+            if suggestion.parent != current {
+                let patch = server.get_patch(&suggestion.parent)?;
+                caught_up.insert(id.clone(), suggestion.clone().transform(&patch));
+            }
+        }
+        self.suggestions.extend(caught_up);
+        Ok(())
+    }
+
+    // the content of the server with the suggestion applied
+    pub fn preview(&self, server: &Server<O>, id: &SuggestionId) -> Result<O::Target, String> {
+        let suggestion = self.get(id).ok_or("no such suggestion")?;
+        let patch = server.get_patch(&suggestion.parent)?;
+        let suggestion = suggestion.clone().transform(&patch);
+        Ok(suggestion.diff.apply(&server.current_state().content))
+    }
+
+    // apply the suggestion to the server, as made by its author
+    // the suggestion is kept if the server refuses it, e.g. when a validator rejects it
+    pub fn accept(
+        &mut self,
+        server: &mut Server<O>,
+        id: &SuggestionId,
+    ) -> Result<Patch<O>, String> {
+        let suggestion = self.get(id).ok_or("no such suggestion")?.clone();
+        let metadata = Metadata {
+            session: Some(suggestion.author),
+            ..Metadata::default()
+        };
+        let patch = server.modify_with(metadata, suggestion.parent, suggestion.diff)?;
+        self.suggestions.remove(id);
+        Ok(patch)
+    }
+
+    pub fn reject(&mut self, id: &SuggestionId) -> Result<Suggestion<O>, String> {
+        self.suggestions
+            .remove(id)
+            .ok_or_else(|| "no such suggestion".into())
+    }
+}
@@ -0,0 +1,315 @@
+use super::*;
+use super::super::Operation;
+use super::listener::{Incoming, Listener, Transport};
+use super::protocol::{Message, Reply, Request};
+use super::client;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+extern crate serde_json;
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+extern crate futures;
+use self::futures::{Future, FutureExt};
+use self::futures::channel::oneshot;
+
+// the longest frame which is read or written
+// the length comes from the peer, so it is limited before allocating the buffer for the message
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+// every frame is a 4 byte big endian length followed by a JSON message of that length
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    let body =
+        serde_json::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let len = body.len();
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame too large"));
+    }
+
+    // written at once, so that a frame is not split into several packets
+    let mut frame = Vec::with_capacity(4 + len);
+    frame.extend_from_slice(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8]);
+    frame.extend_from_slice(&body);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+// the length of the message from the header of a frame
+fn frame_len(header: &[u8]) -> io::Result<usize> {
+    let len = header
+        .iter()
+        .fold(0, |len, &byte| (len << 8) | byte as usize);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    Ok(len)
+}
+
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> io::Result<T> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header)?;
+    let len = frame_len(&header)?;
+
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Debug, Fail)]
+pub enum TcpConnectionError {
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] io::Error),
+    #[fail(display = "Server error: {}", _0)]
+    Server(String),
+    #[fail(display = "Unexpected reply from the server")]
+    UnexpectedReply,
+    #[fail(display = "Connection closed")]
+    Disconnected,
+    #[fail(display = "Connection poisoned by a panicked thread")]
+    Poisoned,
+}
+
+impl From<io::Error> for TcpConnectionError {
+    fn from(error: io::Error) -> Self {
+        TcpConnectionError::Io(error)
+    }
+}
+
+type ReplySender<O> = oneshot::Sender<Result<Reply<O>, String>>;
+
+// the client side of a TCP connection
+// a background thread reads the messages from the server, so requests do not block and
+// several of them can be in flight
+pub struct TcpConnection<O: Operation> {
+    client_id: ClientId,
+    stream: TcpStream,
+    // held while registering and writing a request, so that requests are written in the order
+    // of the senders
+    writer: Mutex<TcpStream>,
+    // senders for the replies to the requests in flight, in the order of the requests
+    pending: Arc<Mutex<VecDeque<ReplySender<O>>>>,
+    // the last state pushed by the server
+    pushed: Arc<Mutex<Option<State<O>>>>,
+}
+
+impl<O> TcpConnection<O>
+where
+    O: Operation + Serialize + DeserializeOwned + Send + 'static,
+    O::Target: Serialize + DeserializeOwned + Send,
+{
+    // connect to a TcpServer and wait for its greeting
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self, TcpConnectionError> {
+        let mut stream = TcpStream::connect(addr)?;
+        let greeting: Message<O> = read_frame(&mut stream)?;
+        let client_id = match greeting {
+            Message::Hello(client_id) => client_id,
+            _ => return Err(TcpConnectionError::UnexpectedReply),
+        };
+
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+        let pushed = Arc::new(Mutex::new(None));
+        {
+            let mut stream = stream.try_clone()?;
+            let pending = pending.clone();
+            let pushed = pushed.clone();
+            thread::spawn(move || Self::receive(&mut stream, &pending, &pushed));
+        }
+
+        Ok(TcpConnection {
+            client_id: client_id,
+            writer: Mutex::new(stream.try_clone()?),
+            stream: stream,
+            pending: pending,
+            pushed: pushed,
+        })
+    }
+
+    // runs until the connection is closed
+    // the requests in flight are canceled then
+    fn receive(
+        stream: &mut TcpStream,
+        pending: &Mutex<VecDeque<ReplySender<O>>>,
+        pushed: &Mutex<Option<State<O>>>,
+    ) {
+        while let Ok(message) = read_frame(stream) {
+            match message {
+                Message::Hello(_) => break,
+                Message::Push(state) => {
+                    // states made at the same time may be pushed out of order
+                    let mut pushed = pushed.lock().unwrap();
+                    if pushed.as_ref().map_or(true, |pushed| pushed.id < state.id) {
+                        *pushed = Some(state);
+                    }
+                }
+                Message::Reply(reply) => match pending
+                    .lock()
+                    .ok()
+                    .and_then(|mut pending| pending.pop_front())
+                {
+                    Some(sender) => {
+                        let _ = sender.send(reply);
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        let _ = stream.shutdown(Shutdown::Both);
+        if let Ok(mut pending) = pending.lock() {
+            pending.clear();
+        }
+    }
+
+    pub fn pushed_state(&self) -> Option<State<O>> {
+        self.pushed.lock().unwrap().clone()
+    }
+
+    fn request(
+        &self,
+        request: &Request<O>,
+    ) -> Box<Future<Item = Reply<O>, Error = TcpConnectionError>> {
+        use self::futures::future::{err, ok};
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            // the pending lock is released before writing, so that the receiving thread can
+            // hand over replies while a large request is written
+            let mut writer = match self.writer.lock() {
+                Ok(writer) => writer,
+                Err(_) => return Box::new(err(TcpConnectionError::Poisoned)),
+            };
+            match self.pending.lock() {
+                Ok(mut pending) => pending.push_back(sender),
+                Err(_) => return Box::new(err(TcpConnectionError::Poisoned)),
+            }
+            if let Err(e) = write_frame(&mut *writer, request) {
+                // a partially written frame breaks the connection, which cancels the requests
+                // in flight
+                let _ = self.stream.shutdown(Shutdown::Both);
+                return Box::new(err(e.into()));
+            }
+        }
+
+        Box::new(
+            receiver
+                .map_err(|_| TcpConnectionError::Disconnected)
+                .and_then(|reply| match reply {
+                    Ok(reply) => ok(reply),
+                    Err(s) => err(TcpConnectionError::Server(s)),
+                }),
+        )
+    }
+
+    fn request_patch(
+        &self,
+        request: &Request<O>,
+    ) -> Box<Future<Item = Patch<O>, Error = TcpConnectionError>> {
+        Box::new(self.request(request).and_then(|reply| match reply {
+            Reply::Patch(patch) => Ok(patch),
+            _ => Err(TcpConnectionError::UnexpectedReply),
+        }))
+    }
+}
+
+impl<O: Operation> Drop for TcpConnection<O> {
+    fn drop(&mut self) {
+        // stop the receiving thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl<O> client::Connection<O> for TcpConnection<O>
+where
+    O: Operation + Serialize + DeserializeOwned + Send + 'static,
+    O::Target: Serialize + DeserializeOwned + Send,
+{
+    type Error = TcpConnectionError;
+    type Output = Box<Future<Item = Patch<O>, Error = Self::Error>>;
+    type StateFuture = Box<Future<Item = State<O>, Error = Self::Error>>;
+
+    fn client_id(&self) -> ClientId {
+        self.client_id.clone()
+    }
+
+    fn get_latest_state(&self) -> Self::StateFuture {
+        Box::new(
+            self.request(&Request::GetLatestState)
+                .and_then(|reply| match reply {
+                    Reply::State(state) => Ok(state),
+                    _ => Err(TcpConnectionError::UnexpectedReply),
+                }),
+        )
+    }
+
+    fn get_patch_since(&self, since_id: &Id) -> Self::Output {
+        self.request_patch(&Request::GetPatchSince(since_id.clone()))
+    }
+
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        self.request_patch(&Request::SendOperation(submission))
+    }
+}
+
+// the server side of a TCP connection
+// the bytes of a partially received frame are kept until the rest of it arrives
+pub struct TcpTransport {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl<O> Transport<O> for TcpTransport
+where
+    O: Operation + Serialize + DeserializeOwned,
+    O::Target: Serialize + DeserializeOwned,
+{
+    type Error = io::Error;
+
+    fn open(stream: TcpStream) -> io::Result<Self> {
+        Ok(TcpTransport {
+            stream: stream,
+            buffer: vec![],
+        })
+    }
+
+    fn receive(&mut self) -> io::Result<Incoming<O>> {
+        loop {
+            if self.buffer.len() >= 4 {
+                let len = frame_len(&self.buffer[..4])?;
+                if self.buffer.len() >= 4 + len {
+                    let request = serde_json::from_slice(&self.buffer[4..4 + len])
+                        .map_err(|e| format!("invalid request: {}", e));
+                    self.buffer.drain(..4 + len);
+                    return Ok(Incoming::Request(request));
+                }
+            }
+
+            let mut chunk = [0; 4096];
+            match self.stream.read(&mut chunk) {
+                // the client disconnected
+                Ok(0) => return Ok(Incoming::Closed),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(Incoming::Idle)
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn send(&mut self, message: &Message<O>) -> io::Result<()> {
+        write_frame(&mut self.stream, message)
+    }
+}
+
+// accepts TCP connections and serves them with a Server, a thread for each
+pub type TcpServer<O> = Listener<O, TcpTransport>;
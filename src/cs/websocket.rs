@@ -0,0 +1,143 @@
+// a WebSocket endpoint for clients which cannot open raw TCP connections, e.g. browsers
+//
+// every message is a text message containing a JSON object or string.
+// the messages are the ones of cs::protocol, encoded as serde's externally tagged enums:
+//
+// from the server
+//   {"Hello": 0}
+//       the first message, with the client id to put into submissions
+//   {"Push": <state>}
+//       the latest state of the server, sent right after Hello and after every request
+//       changing it, from any client
+//   {"Reply": {"Ok": {"State": <state>}}}
+//   {"Reply": {"Ok": {"Patch": <patch>}}}
+//   {"Reply": {"Err": "index out of range"}}
+//       the reply to a request. every request gets exactly one reply, in the order of the
+//       requests. a request which can not be parsed is replied with an Err
+//
+// from a client
+//   "GetLatestState"
+//       replied with a State
+//   {"GetPatchSince": 1}
+//       replied with a Patch from state 1 to the latest one
+//   {"SendOperation": {"client_id": 0, "seq": 1, "parent": 1, "diff": ..., "acknowledged": 0}}
+//       replied with a Patch to be applied after the operation. seq starts from 1 and is
+//       incremented for each new operation. resending an operation with the same seq does
+//       not apply it twice. acknowledged is the seq of the last reply the client applied,
+//       0 before the first one. it may be omitted, and then the server guesses it from parent
+//
+// a state is
+//   {"parent": 0, "id": 1, "diff": ..., "content": ..., "checksum": 123, "metadata": <metadata>}
+//   parent     the id of the state diff was applied to
+//   id         the index of the state in the history of the server
+//   diff       the operation which made the state
+//   content    the content of the document at the state
+//   checksum   the checksum of content, or null
+//   metadata   how the state was made
+//
+// a patch is
+//   {"id": 1, "diff": ..., "checksum": 123, "metadata": [<metadata>, ...], "rejection": null,
+//    "from_parent": false}
+//   id         the state the patch brings the client to
+//   diff       the operation to apply to the content the client had
+//   checksum   the checksum of the content of state id, or null
+//   metadata   the metadata of the states diff brings the client through, oldest first.
+//              only the newest ones are included, 64 unless the server is configured
+//              otherwise, so it may be shorter than the number of states or empty
+//   rejection  null, or {"code": "protected", "reason": "..."} if the server rejected the
+//              sent operation. diff then undoes the operation as well. code is meant for
+//              programs and reason for users
+//   from_parent
+//              true along with a rejection if the operation can not be undone. diff then
+//              applies to the content before the sent operation, and the client drops the
+//              operation along with the ones it made after it
+//
+// metadata is
+//   {"author": "alice", "timestamp": 1530000000000, "session": 0, "fields": {"key": "value"}}
+//   author     the user who made the state, or null
+//   timestamp  milliseconds since the unix epoch when the server accepted the state
+//   session    the client id the operation came from, or null if the server made it
+//   fields     strings the application keeps along with the state
+//
+// diff is an operation and content is the target of the operation.
+// for charwise::Operation, they are
+//   {"operations": [{"Retain": 3}, {"Insert": "abc"}, {"Delete": 2}], "source_len": 5,
+//    "target_len": 6}
+//   "content"
+// where the lengths are in bytes of UTF-8. for linewise::Operation, content is an array of
+// the lines without line breaks
+//
+// the checksum is the 64 bit FNV-1a hash (offset basis 0xcbf29ce484222325, prime
+// 0x100000001b3) of these bytes:
+//   charwise  the length of the content in bytes as a little-endian u64, then its UTF-8 bytes
+//   linewise  the number of lines as a little-endian u64, then each line encoded as charwise
+// it may not fit into a number of JavaScript. parse it e.g. as BigInt to compare it
+
+use super::super::Operation;
+use super::listener::{Incoming, Listener, Transport};
+use super::protocol::Message;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+extern crate serde_json;
+extern crate tungstenite;
+
+use self::tungstenite::{Error, WebSocket};
+use self::tungstenite::Message as WebSocketMessage;
+
+use std::io;
+use std::net::TcpStream;
+
+// the server side of a WebSocket connection
+pub struct WebSocketTransport {
+    socket: WebSocket<TcpStream>,
+}
+
+impl<O> Transport<O> for WebSocketTransport
+where
+    O: Operation + Serialize + DeserializeOwned,
+    O::Target: Serialize + DeserializeOwned,
+{
+    type Error = Error;
+
+    fn open(stream: TcpStream) -> tungstenite::Result<Self> {
+        let socket = tungstenite::accept(stream)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        Ok(WebSocketTransport { socket: socket })
+    }
+
+    fn receive(&mut self) -> tungstenite::Result<Incoming<O>> {
+        loop {
+            let request = match self.socket.read_message() {
+                Ok(WebSocketMessage::Text(text)) => serde_json::from_str(&text),
+                Ok(WebSocketMessage::Binary(bytes)) => serde_json::from_slice(&bytes),
+                Err(Error::ConnectionClosed(_)) | Err(Error::AlreadyClosed) => {
+                    return Ok(Incoming::Closed)
+                }
+                // tungstenite keeps a partially read message
+                Err(Error::Io(ref e))
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(Incoming::Idle)
+                }
+                // pings and closing handshakes are answered by tungstenite
+                Ok(_) => continue,
+                Err(e) => return Err(e),
+            };
+            return Ok(Incoming::Request(
+                request.map_err(|e| format!("invalid request: {}", e)),
+            ));
+        }
+    }
+
+    fn send(&mut self, message: &Message<O>) -> tungstenite::Result<()> {
+        let text = serde_json::to_string(message)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.socket.write_message(WebSocketMessage::Text(text))
+    }
+}
+
+// accepts WebSocket connections and serves them with a Server, a thread for each
+pub type WebSocketServer<O> = Listener<O, WebSocketTransport>;
@@ -1,4 +1,5 @@
 use std::default::Default;
+use std::hash::Hasher;
 
 extern crate serde;
 #[macro_use]
@@ -15,6 +16,9 @@ pub use cs::client;
 pub mod charwise;
 pub mod linewise;
 pub mod selection;
+pub mod anchor;
+pub mod comment;
+pub mod protection;
 
 pub trait Operation: Sized + Default + Clone {
     type Target: Default + Clone;
@@ -33,4 +37,86 @@ pub trait Operation: Sized + Default + Clone {
     // let (left', right') = transform(left, right), these satisfies the condition
     // apply(s, compose(left, right')) == apply(s, compose(right, left'))
     fn transform(self, other: Self) -> (Self, Self);
+
+    // a hash of target used to detect replicas diverging from each other
+    // None if the operation does not support checksums
+    fn checksum(_target: &Self::Target) -> Option<u64> {
+        None
+    }
+
+    // an operation which undoes self applied to source, i.e.
+    // apply(apply(source, self), invert(self, source)) == source
+    // None if the operation does not support inversion
+    fn invert(&self, _source: &Self::Target) -> Option<Self> {
+        None
+    }
+}
+
+// FNV-1a, which gives the same hash on every build unlike DefaultHasher
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn write_length<H: Hasher>(hasher: &mut H, length: usize) {
+    let length = length as u64;
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (length >> (8 * i)) as u8;
+    }
+    hasher.write(&bytes);
+}
+
+// the bytes of a content which are hashed for checksums
+// this does not depend on the platform or the version of the standard library:
+// - a string is its length in bytes as a little-endian u64 followed by its UTF-8 bytes
+// - a list is its number of elements as a little-endian u64 followed by each element
+// so a linewise content is the number of lines followed by each line encoded as a string
+pub trait Encode {
+    fn encode<H: Hasher>(&self, hasher: &mut H);
+}
+
+impl Encode for str {
+    fn encode<H: Hasher>(&self, hasher: &mut H) {
+        write_length(hasher, self.len());
+        hasher.write(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode<H: Hasher>(&self, hasher: &mut H) {
+        self.as_str().encode(hasher);
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode<H: Hasher>(&self, hasher: &mut H) {
+        write_length(hasher, self.len());
+        for element in self.iter() {
+            element.encode(hasher);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<H: Hasher>(&self, hasher: &mut H) {
+        self.as_slice().encode(hasher);
+    }
+}
+
+// 64-bit FNV-1a of the encoding of target
+pub fn hash_content<T: Encode + ?Sized>(target: &T) -> u64 {
+    let mut hasher = FnvHasher(0xcbf29ce484222325);
+    target.encode(&mut hasher);
+    hasher.finish()
 }
@@ -98,6 +98,41 @@ impl Default for Operation {
 impl super::Operation for Operation {
     type Target = Vec<String>;
 
+    fn checksum(target: &Self::Target) -> Option<u64> {
+        Some(super::hash_content(target))
+    }
+
+    fn invert(&self, source: &Self::Target) -> Option<Self> {
+        let mut source = source as &[String];
+        let mut ret = Operation::new();
+
+        for op in self.operations.iter() {
+            use self::LineOperation::*;
+
+            match *op {
+                Retain(len) => {
+                    ret.retain(len);
+                    source = &source[len..];
+                }
+                Delete(len) => {
+                    for line in source[0..len].iter() {
+                        ret.insert(line.clone());
+                    }
+                    source = &source[len..];
+                }
+                Insert(_) => {
+                    ret.delete(1);
+                }
+                Modify(ref op) => {
+                    ret.modify(op.invert(&source[0])?);
+                    source = &source[1..];
+                }
+            }
+        }
+
+        Some(ret)
+    }
+
     fn nop(target: &Self::Target) -> Self {
         let mut ret = Operation::new();
         ret.retain(target.len());
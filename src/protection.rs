@@ -0,0 +1,317 @@
+// read-only ranges of a document, e.g. generated headers or license blocks
+// the ranges are a part of the content stored and synced through the server, and they follow
+// the edits of the document like selections do. the server rejects operations editing inside
+// them when a Guard is added as its validator, and clients can check operations against the
+// ranges of their unsynced content before sending them
+
+use super::Operation as OperationTrait;
+use super::charwise;
+use super::linewise;
+use super::anchor::Document;
+use super::selection::Bias;
+use super::selection::linewise::Position;
+use super::cs::{Metadata, Rejection, State};
+use super::cs::server::Validator;
+
+// documents with ranges which can be protected
+pub trait Protect: Document {
+    // whether the operation inserts or deletes anything inside the range from start to end
+    // inserting at either end is not editing inside the range
+    fn touches(&self, start: &Self::Position, end: &Self::Position) -> bool;
+}
+
+// the range of bytes of a line an operation edits inside of
+// None means the range continues to the previous or the next line
+fn touches_line(op: &charwise::Operation, start: Option<usize>, end: Option<usize>) -> bool {
+    use charwise::PrimitiveOperation::*;
+
+    let mut index = 0;
+    for op in op.operations.iter() {
+        match *op {
+            Retain(len) => index += len,
+            Insert(_) => {
+                if start.map_or(true, |start| start < index)
+                    && end.map_or(true, |end| index < end)
+                {
+                    return true;
+                }
+            }
+            Delete(len) => {
+                if start.map_or(true, |start| start < index + len)
+                    && end.map_or(true, |end| index < end)
+                {
+                    return true;
+                }
+                index += len;
+            }
+        }
+    }
+    false
+}
+
+impl Protect for charwise::Operation {
+    fn touches(&self, start: &usize, end: &usize) -> bool {
+        touches_line(self, Some(*start), Some(*end))
+    }
+}
+
+impl Protect for linewise::Operation {
+    // deleting a line removes its line break as well, so it edits a range starting at the end
+    // of the line
+    fn touches(&self, start: &Position, end: &Position) -> bool {
+        use linewise::LineOperation::*;
+
+        let line = |row| Position { row: row, col: 0 };
+        let mut row = 0;
+        for op in self.operations.iter() {
+            match *op {
+                Retain(len) => row += len,
+                Insert(_) => {
+                    if *start < line(row) && line(row) < *end {
+                        return true;
+                    }
+                }
+                Modify(ref op) => {
+                    if start.row <= row && row <= end.row {
+                        let start = if row == start.row { Some(start.col) } else { None };
+                        let end = if row == end.row { Some(end.col) } else { None };
+                        if touches_line(op, start, end) {
+                            return true;
+                        }
+                    }
+                    row += 1;
+                }
+                Delete(len) => {
+                    if *start < line(row + len) && line(row) < *end {
+                        return true;
+                    }
+                    row += len;
+                }
+            }
+        }
+        false
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Range<P> {
+    pub start: P,
+    pub end: P,
+}
+
+impl<P: Clone + Ord> Range<P> {
+    pub fn new(start: P, end: P) -> Self {
+        Range {
+            start: start,
+            end: end,
+        }
+    }
+
+    // text inserted at either end is not a part of the range
+    pub fn transform<O: Document<Position = P>>(mut self, op: &O) -> Self {
+        O::transform_position(&mut self.start, Bias::Right, op);
+        O::transform_position(&mut self.end, Bias::Left, op);
+        if self.start > self.end {
+            self.start = self.end.clone();
+        }
+        self
+    }
+}
+
+fn transform_ranges<O: Document>(
+    ranges: &[Range<O::Position>],
+    op: &O,
+) -> Vec<Range<O::Position>> {
+    ranges
+        .iter()
+        .map(|range| range.clone().transform(op))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O::Target: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O::Target: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Target<O: Document> {
+    pub base: O::Target,
+    pub ranges: Vec<Range<O::Position>>,
+}
+
+// derive would require O: PartialEq instead of O::Target: PartialEq
+impl<O: Document> PartialEq for Target<O>
+where
+    O::Target: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base && self.ranges == other.ranges
+    }
+}
+
+impl<O: Document> Eq for Target<O>
+where
+    O::Target: Eq,
+{
+}
+
+impl<O: Document> Default for Target<O> {
+    fn default() -> Self {
+        Target {
+            base: O::Target::default(),
+            ranges: vec![],
+        }
+    }
+}
+
+impl<O: Document> Target<O> {
+    pub fn operate(&self, base: O) -> Operation<O> {
+        Operation {
+            ranges: Some(transform_ranges(&self.ranges, &base)),
+            changed: false,
+            base: base,
+        }
+    }
+}
+
+impl<O: Protect> Target<O> {
+    // check an edit of the content before applying or sending it
+    pub fn check(&self, op: &O) -> Result<(), Rejection> {
+        let touched = self.ranges
+            .iter()
+            .any(|range| range.start < range.end && op.touches(&range.start, &range.end));
+        if touched {
+            Err(Rejection::new("protected", "a protected range can not be edited"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// an edit of the document along with the ranges after it
+// like anchor::Operation, the ranges moved by the edit may depend on the order concurrent
+// edits are applied in. make operations with Target::operate, which has every range, so that
+// replicas converge
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound(serialize = "O: ::serde::Serialize, O::Position: ::serde::Serialize",
+              deserialize = "O: ::serde::Deserialize<'de>, \
+                             O::Position: ::serde::Deserialize<'de>"))]
+pub struct Operation<O: Document> {
+    // ranges on the target of base, None to move the ranges of the target along with base
+    pub ranges: Option<Vec<Range<O::Position>>>,
+    // whether the operation protected or unprotected ranges, which wins in transform
+    pub changed: bool,
+    pub base: O,
+}
+
+impl<O: Document> Operation<O> {
+    pub fn with_content(base: O) -> Self {
+        Operation {
+            ranges: None,
+            changed: false,
+            base: base,
+        }
+    }
+
+    pub fn protect(&mut self, start: O::Position, end: O::Position) -> &mut Self {
+        self.changed = true;
+        self.ranges
+            .get_or_insert_with(Vec::new)
+            .push(Range::new(start, end));
+        self
+    }
+
+    // remove the ranges from start to end
+    pub fn unprotect(&mut self, start: O::Position, end: O::Position) -> &mut Self {
+        let range = Range::new(start, end);
+        self.changed = true;
+        self.ranges
+            .get_or_insert_with(Vec::new)
+            .retain(|protected| *protected != range);
+        self
+    }
+}
+
+impl<O: Document> Default for Operation<O> {
+    fn default() -> Self {
+        Operation::with_content(O::default())
+    }
+}
+
+impl<O: Document> OperationTrait for Operation<O> {
+    type Target = Target<O>;
+
+    fn nop(target: &Self::Target) -> Self {
+        target.operate(O::nop(&target.base))
+    }
+
+    fn apply(&self, target: &Self::Target) -> Self::Target {
+        Target {
+            base: self.base.apply(&target.base),
+            ranges: match self.ranges {
+                Some(ref ranges) => ranges.clone(),
+                None => transform_ranges(&target.ranges, &self.base),
+            },
+        }
+    }
+
+    fn compose(self, other: Self) -> Self {
+        let ranges = match other.ranges {
+            Some(ranges) => Some(ranges),
+            None => self.ranges
+                .map(|ranges| transform_ranges(&ranges, &other.base)),
+        };
+        Operation {
+            ranges: ranges,
+            changed: self.changed || other.changed,
+            base: self.base.compose(other.base),
+        }
+    }
+
+    // both operations get the same ranges. self's are adopted only if self changed them, so
+    // that an operation of a client can not move the ranges of the server unless it changes
+    // them, which Guard rejects
+    fn transform(self, other: Self) -> (Self, Self) {
+        let (lhs, rhs) = self.base.transform(other.base);
+        let lhs_ranges = self.ranges.map(|ranges| transform_ranges(&ranges, &rhs));
+        let rhs_ranges = other.ranges.map(|ranges| transform_ranges(&ranges, &lhs));
+        let ranges = if self.changed {
+            lhs_ranges.or(rhs_ranges)
+        } else {
+            rhs_ranges.or(lhs_ranges)
+        };
+
+        (
+            Operation {
+                ranges: ranges.clone(),
+                changed: self.changed,
+                base: lhs,
+            },
+            Operation {
+                ranges: ranges,
+                changed: other.changed,
+                base: rhs,
+            },
+        )
+    }
+}
+
+// a validator rejecting edits inside the protected ranges of the current state
+// the ranges are changed by the application through the server, e.g. with modify, and
+// operations from clients changing them are rejected as well
+pub struct Guard;
+
+impl<O: Protect> Validator<Operation<O>> for Guard {
+    fn validate(
+        &self,
+        state: &State<Operation<O>>,
+        operation: &Operation<O>,
+        metadata: &Metadata,
+    ) -> Result<(), Rejection> {
+        if operation.changed && metadata.session.is_some() {
+            return Err(Rejection::new(
+                "protected",
+                "protected ranges can not be changed by clients",
+            ));
+        }
+        state.content.check(&operation.base)
+    }
+}
@@ -1,16 +1,23 @@
 use super::super::Operation as OperationTrait;
 use super::super::charwise::Operation as BaseOperation;
+use super::super::cs::presence::Transform;
+use super::Bias;
 
 use std::default::Default;
+use std::collections::HashMap;
+use std::hash::Hash;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Selection {
-    Cursor(usize),
-    Range(usize, usize),
+    Cursor(usize, Bias),
+    // anchor, head and their biases
+    // the anchor is where the selection started and the head is where the cursor is,
+    // so the head is before the anchor when selected backward
+    Range(usize, usize, Bias, Bias),
 }
 
 impl Selection {
-    pub(crate) fn transform_index(value: &mut usize, op: &BaseOperation) {
+    pub(crate) fn transform_index(value: &mut usize, bias: Bias, op: &BaseOperation) {
         use charwise::PrimitiveOperation::*;
 
         let mut idx = 0;
@@ -20,7 +27,7 @@ impl Selection {
                     idx += len;
                 }
                 Insert(ref s) => {
-                    if idx <= *value {
+                    if idx < *value || (idx == *value && bias == Bias::Right) {
                         *value += s.len();
                     }
                     idx += s.len();
@@ -34,83 +41,148 @@ impl Selection {
         }
     }
 
-    fn transform(mut self, op: &BaseOperation) -> Option<Self> {
+    fn transform(self, op: &BaseOperation) -> Option<Self> {
         use self::Selection::*;
 
         match self {
-            Cursor(ref mut pos) => Self::transform_index(pos, op),
-            Range(ref mut start, ref mut end) => {
-                Self::transform_index(start, op);
-                Self::transform_index(end, op);
-
-                // TODO: verify this
-                if *start == *end {
-                    return None;
+            Cursor(mut pos, bias) => {
+                Self::transform_index(&mut pos, bias, op);
+                Some(Cursor(pos, bias))
+            }
+            Range(mut anchor, mut head, anchor_bias, head_bias) => {
+                Self::transform_index(&mut anchor, anchor_bias, op);
+                Self::transform_index(&mut head, head_bias, op);
+
+                // the selected text was deleted, but the cursor remains
+                if anchor == head {
+                    Some(Cursor(head, head_bias))
+                } else {
+                    Some(Range(anchor, head, anchor_bias, head_bias))
                 }
             }
         }
+    }
+
+    pub fn anchor(&self) -> usize {
+        match *self {
+            Selection::Cursor(pos, _) => pos,
+            Selection::Range(anchor, _, _, _) => anchor,
+        }
+    }
+
+    pub fn head(&self) -> usize {
+        match *self {
+            Selection::Cursor(pos, _) => pos,
+            Selection::Range(_, head, _, _) => head,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor().min(self.head())
+    }
 
-        Some(self)
+    pub fn end(&self) -> usize {
+        self.anchor().max(self.head())
+    }
+
+    pub fn is_backward(&self) -> bool {
+        self.head() < self.anchor()
+    }
+}
+
+// selections shared as presence
+impl Transform<BaseOperation> for Selection {
+    fn transform(self, op: &BaseOperation) -> Option<Self> {
+        Selection::transform(self, op)
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct Target {
+pub struct Target<UserId: Clone + Eq + Hash> {
     pub base: <BaseOperation as OperationTrait>::Target,
-    pub selection: Vec<Selection>,
+    pub selection: HashMap<UserId, Vec<Selection>>,
 }
 
-impl Default for Target {
+impl<UserId: Clone + Eq + Hash> Default for Target<UserId> {
     fn default() -> Self {
         Target {
             base: <BaseOperation as OperationTrait>::Target::default(),
-            selection: vec![],
+            selection: HashMap::new(),
         }
     }
 }
 
+impl<UserId: Clone + Eq + Hash> Target<UserId> {
+    pub fn operate(&self, op: BaseOperation) -> Operation<UserId> {
+        let selection = self.selection
+            .iter()
+            .map(|(id, s)| {
+                (
+                    id.clone(),
+                    s.iter().cloned().filter_map(|s| s.transform(&op)).collect(),
+                )
+            })
+            .collect();
+        Operation::Op(selection, op)
+    }
+
+    pub fn select(&self, s: HashMap<UserId, Vec<Selection>>) -> Operation<UserId> {
+        Operation::Op(s, {
+            let mut op = BaseOperation::new();
+            op.retain(self.base.len());
+            op
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum Operation {
+pub enum Operation<UserId: Clone + Eq + Hash> {
     Nop,
-    Select(Vec<Selection>),
-    Operate(BaseOperation),
-    Both(Vec<Selection>, BaseOperation),
+    Op(HashMap<UserId, Vec<Selection>>, BaseOperation),
 }
 
-impl Default for Operation {
+impl<UserId: Clone + Eq + Hash> Operation<UserId> {
+    pub fn with_content(op: BaseOperation) -> Self {
+        Operation::Op(HashMap::new(), op)
+    }
+
+    pub fn operate(&self, op: BaseOperation) -> Self {
+        use self::Operation::*;
+
+        match *self {
+            Nop => Op(HashMap::new(), op),
+            Op(ref s, _) => {
+                let s = s.iter()
+                    .map(|(ref k, ref v)| {
+                        let v = v.iter().cloned().filter_map(|s| s.transform(&op)).collect();
+                        ((*k).clone(), v)
+                    })
+                    .collect();
+                Op(s, op)
+            }
+        }
+    }
+}
+
+impl<UserId: Clone + Eq + Hash> Default for Operation<UserId> {
     fn default() -> Self {
         Operation::Nop
     }
 }
 
-impl OperationTrait for Operation {
-    type Target = Target;
+impl<UserId: Clone + Eq + Hash> OperationTrait for Operation<UserId> {
+    type Target = Target<UserId>;
 
-    fn nop(_: &Target) -> Self {
+    fn nop(_: &Self::Target) -> Self {
         Operation::Nop
     }
 
-    fn apply(&self, target: &Target) -> Target {
+    fn apply(&self, target: &Self::Target) -> Self::Target {
         use self::Operation::*;
 
         match *self {
             Nop => target.clone(),
-            Select(ref s) => Target {
-                base: target.base.clone(),
-                selection: s.clone(),
-            },
-            Operate(ref op) => {
-                let base = op.apply(&target.base);
-                let selection = target
-                    .selection
-                    .iter()
-                    .cloned()
-                    .filter_map(|s| s.transform(op))
-                    .collect();
-
-                Target { base, selection }
-            }
-            Both(ref s, ref op) => {
+            Op(ref s, ref op) => {
                 let base = op.apply(&target.base);
                 let selection = s.clone();
 
@@ -125,57 +197,35 @@ impl OperationTrait for Operation {
         match (self, other) {
             (Nop, other) => other,
             (this, Nop) => this,
-            (Select(_), Select(s)) => Select(s),
-            (Select(s), Operate(o)) => {
-                let selection = s.into_iter().filter_map(|s| s.transform(&o)).collect();
-                Both(selection, o)
-            }
-            (Select(_), Both(s, o)) | (Operate(o), Select(s)) | (Both(_, o), Select(s)) => {
-                Both(s, o)
-            }
-            (Operate(lhs), Operate(rhs)) => Operate(lhs.compose(rhs)),
-            (Operate(lhs), Both(s, rhs)) | (Both(_, lhs), Both(s, rhs)) => {
-                Both(s, lhs.compose(rhs))
-            }
-            (Both(s, lhs), Operate(rhs)) => {
-                let s = s.into_iter().filter_map(|s| s.transform(&rhs)).collect();
-                Both(s, lhs.compose(rhs))
-            }
+            (Op(_, lhs), Op(s, rhs)) => Op(s, lhs.compose(rhs)),
         }
     }
 
-    // when each operation contains Select, tie break by adopting self's
+    // when each operation contains selections of the same user, tie break by adopting self's
     fn transform(self, other: Self) -> (Self, Self) {
         use self::Operation::*;
 
         match (self, other) {
             (Nop, other) => (Nop, other),
             (this, Nop) => (this, Nop),
-            (Select(s), Select(_)) => (Select(s), Nop),
-            (Select(s), Operate(o)) | (Select(s), Both(_, o)) => {
-                let selection = s.into_iter().filter_map(|s| s.transform(&o)).collect();
-                (Select(selection), Operate(o))
-            }
-            (Operate(o), Select(s)) => {
-                let selection = s.into_iter().filter_map(|s| s.transform(&o)).collect();
-                (Operate(o), Select(selection))
-            }
-            (Operate(lhs), Operate(rhs)) => {
-                let (lhs_, rhs_) = lhs.transform(rhs);
-                (Operate(lhs_), Operate(rhs_))
-            }
-            (Operate(lhs), Both(s, rhs)) => {
-                let (lhs_, rhs_) = lhs.transform(rhs);
-                let selection = s.into_iter().filter_map(|s| s.transform(&lhs_)).collect();
-                (Operate(lhs_), Both(selection, rhs_))
-            }
-            (Both(s, o), Select(_)) => (Both(s, o), Nop),
-            (Both(s, lhs), Operate(rhs)) | (Both(s, lhs), Both(_, rhs)) => {
+            (Op(slhs, lhs), Op(srhs, rhs)) => {
                 let (lhs_, rhs_) = lhs.transform(rhs);
-                let selection = s.into_iter()
-                    .filter_map(|s| s.transform(&rhs_))
-                    .collect::<Vec<_>>();
-                (Both(selection.clone(), lhs_), Both(selection, rhs_))
+                let selection: HashMap<UserId, Vec<Selection>> = {
+                    let slhs = slhs.into_iter().map(|(id, s)| {
+                        (
+                            id,
+                            s.into_iter().filter_map(|s| s.transform(&rhs_)).collect(),
+                        )
+                    });
+                    let srhs = srhs.into_iter().map(|(id, s)| {
+                        (
+                            id,
+                            s.into_iter().filter_map(|s| s.transform(&lhs_)).collect(),
+                        )
+                    });
+                    srhs.chain(slhs).collect()
+                };
+                (Op(selection.clone(), lhs_), Op(selection, rhs_))
             }
         }
     }
@@ -1,5 +1,7 @@
 use super::super::Operation as OperationTrait;
 use super::super::linewise::Operation as BaseOperation;
+use super::super::cs::presence::Transform;
+use super::Bias;
 
 use std::default::Default;
 use std::collections::HashMap;
@@ -13,62 +15,123 @@ pub struct Position {
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Selection {
-    Cursor(Position),
-    Range(Position, Position),
+    Cursor(Position, Bias),
+    // anchor, head and their biases
+    // the anchor is where the selection started and the head is where the cursor is,
+    // so the head is before the anchor when selected backward
+    Range(Position, Position, Bias, Bias),
 }
 
 impl Selection {
-    fn transform_index(value: &mut Position, op: &BaseOperation) {
+    // map a position on the source of op to the target
+    // a position on a deleted line moves to the beginning of the line following the deleted
+    // lines, as if the text up to there were deleted. it moves to the beginning of the last
+    // line if nothing follows them
+    pub(crate) fn transform_index(value: &mut Position, bias: Bias, op: &BaseOperation) {
         use linewise::LineOperation::*;
 
-        // index in row
-        let mut idx = 0;
-        for op in op.operations.iter() {
-            println!("idx = {}, op = {:?}", idx, op);
-            match *op {
+        // indices of the current line in the source and the target
+        let mut source = 0;
+        let mut target = 0;
+        let mut deleted = false;
+        for line_op in op.operations.iter() {
+            match *line_op {
                 Retain(len) => {
-                    idx += len;
+                    if value.row < source + len {
+                        value.row = target + (value.row - source);
+                        return;
+                    }
+                    source += len;
+                    target += len;
                 }
                 Insert(_) => {
-                    if idx <= value.row {
-                        value.row += 1;
+                    // a line inserted at the row is text inserted at the beginning of the row
+                    if value.row == source && value.col == 0 && bias == Bias::Left {
+                        value.row = target;
+                        return;
                     }
-                    idx += 1;
+                    target += 1;
                 }
                 Modify(ref op) => {
-                    if idx == value.row {
-                        super::charwise::Selection::transform_index(&mut value.col, op);
+                    if value.row == source {
+                        value.row = target;
+                        super::charwise::Selection::transform_index(&mut value.col, bias, op);
+                        return;
                     }
-                    idx += 1;
+                    source += 1;
+                    target += 1;
                 }
                 Delete(len) => {
-                    if idx < value.row {
-                        value.row -= len.min(value.row - idx);
-                    } else if idx == value.row {
+                    if value.row < source + len {
+                        value.row = source + len;
                         value.col = 0;
+                        deleted = true;
                     }
+                    source += len;
                 }
             }
         }
+
+        // after the end of the source, e.g. on an empty document
+        value.row = target + (value.row - source);
+        if deleted && value.row >= op.target_len() {
+            value.row = op.target_len().saturating_sub(1);
+        }
     }
 
-    fn transform(mut self, op: &BaseOperation) -> Option<Self> {
+    fn transform(self, op: &BaseOperation) -> Option<Self> {
         use self::Selection::*;
 
         match self {
-            Cursor(ref mut pos) => Self::transform_index(pos, op),
-            Range(ref mut start, ref mut end) => {
-                Self::transform_index(start, op);
-                Self::transform_index(end, op);
-
-                // TODO: verify this
-                if *start == *end {
-                    return None;
+            Cursor(mut pos, bias) => {
+                Self::transform_index(&mut pos, bias, op);
+                Some(Cursor(pos, bias))
+            }
+            Range(mut anchor, mut head, anchor_bias, head_bias) => {
+                Self::transform_index(&mut anchor, anchor_bias, op);
+                Self::transform_index(&mut head, head_bias, op);
+
+                // the selected text was deleted, but the cursor remains
+                if anchor == head {
+                    Some(Cursor(head, head_bias))
+                } else {
+                    Some(Range(anchor, head, anchor_bias, head_bias))
                 }
             }
         }
+    }
+
+    pub fn anchor(&self) -> Position {
+        match *self {
+            Selection::Cursor(pos, _) => pos,
+            Selection::Range(anchor, _, _, _) => anchor,
+        }
+    }
+
+    pub fn head(&self) -> Position {
+        match *self {
+            Selection::Cursor(pos, _) => pos,
+            Selection::Range(_, head, _, _) => head,
+        }
+    }
+
+    pub fn start(&self) -> Position {
+        self.anchor().min(self.head())
+    }
+
+    pub fn end(&self) -> Position {
+        self.anchor().max(self.head())
+    }
+
+    pub fn is_backward(&self) -> bool {
+        self.head() < self.anchor()
+    }
+}
 
-        Some(self)
+// selections shared as presence
+impl Transform<BaseOperation> for Selection {
+    fn transform(self, op: &BaseOperation) -> Option<Self> {
+        Selection::transform(self, op)
     }
 }
 
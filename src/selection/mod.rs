@@ -1,2 +1,10 @@
 pub mod charwise;
 pub mod linewise;
+
+// which side an index sticks to when text is inserted exactly at it
+// Left keeps the index before the inserted text, Right moves it after
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bias {
+    Left,
+    Right,
+}
@@ -145,3 +145,28 @@ fn fuzz_test_transform() {
         assert_eq!(left.apply(&original), right.apply(&original));
     }
 }
+
+#[test]
+fn fuzz_test_invert() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..100 {
+        let original_len = rng.gen_range(32, 100);
+        let original = random_string(&mut rng, original_len);
+
+        let op = random_operation(&mut rng, &original);
+        let applied = op.apply(&original);
+        let inverted = op.invert(&original).unwrap();
+
+        assert_eq!(inverted.apply(&applied), original);
+    }
+}
+
+#[test]
+fn test_checksum() {
+    // the length of the content as a little-endian u64 followed by its UTF-8 bytes
+    assert_eq!(Operation::checksum(&"".into()), Some(0xa8c7f832281a39c5));
+    assert_eq!(Operation::checksum(&"世界".into()), Some(0x660da3a8c1b95f55));
+}
@@ -0,0 +1,264 @@
+extern crate ot;
+
+use ot::anchor::*;
+use ot::selection::Bias;
+use ot::charwise::Operation as BaseOperation;
+use ot::Operation as OperationTrait;
+use ot::cs::*;
+use ot::server::*;
+use ot::client::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+mod util;
+use util::{anchor_id, random_bias};
+use util::charwise::random_position;
+
+extern crate rand;
+use rand::Rng;
+
+extern crate futures;
+use futures::executor::block_on;
+
+fn random_target<R: Rng>(rng: &mut R, anchor_num: usize, len: usize) -> Target<BaseOperation> {
+    let base = util::charwise::random_string(rng, len);
+    let anchors = (0..anchor_num)
+        .map(|i| {
+            let anchor = Anchor::new(random_position(rng, &base), random_bias(rng));
+            (anchor_id(&i.to_string()), anchor)
+        })
+        .collect();
+    Target { base, anchors }
+}
+
+fn random_operation<R: Rng>(
+    rng: &mut R,
+    anchor_num: usize,
+    target: &Target<BaseOperation>,
+) -> Operation<BaseOperation> {
+    let base = util::charwise::random_operation(rng, &target.base);
+    let content = base.apply(&target.base);
+    let mut op = target.operate(base);
+    for _ in 0..rng.gen_range(0, 3) {
+        let id = anchor_id(&rng.gen_range(0, anchor_num + 2).to_string());
+        if rng.gen_weighted_bool(3) {
+            op.remove(id);
+        } else {
+            let anchor = Anchor::new(random_position(rng, &content), random_bias(rng));
+            op.put(id, anchor);
+        }
+    }
+    op
+}
+
+#[test]
+fn test_apply() {
+    let target = Target {
+        base: "hello world".into(),
+        anchors: {
+            let mut anchors = BTreeMap::new();
+            anchors.insert(anchor_id("bookmark"), Anchor::new("hello ".len(), Bias::Left));
+            anchors.insert(anchor_id("breakpoint"), Anchor::new("hello ".len(), Bias::Right));
+            anchors.insert(anchor_id("comment"), Anchor::new("hello world".len(), Bias::Right));
+            anchors
+        },
+    };
+
+    // anchors move with the text, and the changed ones are overwritten
+    let mut op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello ".len())
+            .insert("new ".into())
+            .delete("world".len())
+            .insert("世界".into());
+        op
+    });
+    op.remove(anchor_id("comment"))
+        .put(anchor_id("todo"), Anchor::new(0, Bias::Right));
+
+    let applied = op.apply(&target);
+    assert_eq!(applied.base, "hello new 世界");
+    assert_eq!(
+        applied.anchors,
+        {
+            let mut anchors = BTreeMap::new();
+            anchors.insert(anchor_id("bookmark"), Anchor::new("hello ".len(), Bias::Left));
+            anchors.insert(anchor_id("breakpoint"), Anchor::new("hello new 世界".len(), Bias::Right));
+            anchors.insert(anchor_id("todo"), Anchor::new(0, Bias::Right));
+            anchors
+        }
+    );
+
+    // an anchor on deleted text stays at the place of the text
+    let op = applied.operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello".len())
+            .delete(" new ".len())
+            .retain("世界".len());
+        op
+    });
+    assert_eq!(
+        op.apply(&applied).anchors[&anchor_id("bookmark")],
+        Anchor::new("hello".len(), Bias::Left)
+    );
+}
+
+#[test]
+fn test_transform() {
+    let target = Target {
+        base: "abc".into(),
+        anchors: BTreeMap::new(),
+    };
+
+    let mut left = Operation::nop(&target);
+    left.put(anchor_id("bookmark"), Anchor::new(1, Bias::Right));
+    let mut right = Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("xyz".into()).retain(3);
+        op
+    });
+    right
+        .put(anchor_id("bookmark"), Anchor::new(0, Bias::Right))
+        .put(anchor_id("breakpoint"), Anchor::new(4, Bias::Right));
+
+    let (left_, right_) = left.clone().transform(right.clone());
+    let left = left.compose(right_).apply(&target);
+    let right = right.compose(left_).apply(&target);
+    assert_eq!(left, right);
+
+    // the left one wins, moved after the insertion
+    assert_eq!(
+        left.anchors,
+        {
+            let mut anchors = BTreeMap::new();
+            anchors.insert(anchor_id("bookmark"), Anchor::new(4, Bias::Right));
+            anchors.insert(anchor_id("breakpoint"), Anchor::new(4, Bias::Right));
+            anchors
+        }
+    );
+}
+
+#[test]
+fn fuzz_test_compose() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..100 {
+        let len = rng.gen_range(32, 100);
+        let anchor_num = rng.gen_range(1, 30);
+        let target = random_target(&mut rng, anchor_num, len);
+
+        let first = random_operation(&mut rng, anchor_num, &target);
+        let applied = first.apply(&target);
+        let second = random_operation(&mut rng, anchor_num, &applied);
+
+        let double_applied = second.apply(&applied);
+        let compose_applied = first.compose(second).apply(&target);
+
+        assert_eq!(double_applied, compose_applied);
+    }
+}
+
+#[test]
+fn fuzz_test_transform() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(32, 100);
+        let anchor_num = rng.gen_range(1, 30);
+        let target = random_target(&mut rng, anchor_num, len);
+
+        let left = random_operation(&mut rng, anchor_num, &target);
+        let right = random_operation(&mut rng, anchor_num, &target);
+
+        let (left_, right_) = left.clone().transform(right.clone());
+
+        let left = left.compose(right_);
+        let right = right.compose(left_);
+
+        assert_eq!(left.apply(&target), right.apply(&target));
+    }
+}
+
+#[test]
+fn test_transform_changed() {
+    let target = Target {
+        base: "hello".to_string(),
+        anchors: BTreeMap::new(),
+    };
+
+    // an operation transformed against many others keeps only its own changes
+    let mut buffer = target.operate(BaseOperation::nop(&target.base));
+    buffer.put(anchor_id("mine"), Anchor::new(0, Bias::Left));
+    for i in 0..10 {
+        let mut remote = target.operate(BaseOperation::nop(&target.base));
+        remote.put(anchor_id(&i.to_string()), Anchor::new(0, Bias::Right));
+        buffer = buffer.transform(remote).0;
+    }
+    assert_eq!(buffer.changed.len(), 1);
+    assert!(buffer.changed.contains(&anchor_id("mine")));
+}
+
+#[test]
+fn test_charwise_anchor_client_server() {
+    let server: Rc<RefCell<Server<Operation<BaseOperation>>>> =
+        Rc::new(RefCell::new(Server::new()));
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    client1.push_operation({
+        let mut op = Operation::with_content({
+            let mut op = BaseOperation::new();
+            op.insert("fn main() {}".into());
+            op
+        });
+        op.put(anchor_id("breakpoint"), Anchor::new("fn main() {".len(), Bias::Right));
+        op
+    });
+    {
+        let response = client1.send_to_server().unwrap();
+        client1.apply_response(block_on(response).unwrap()).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+
+    // one user edits the text while the other bookmarks it
+    client1.push_operation(client1.current_content().unwrap().operate({
+        let mut op = BaseOperation::new();
+        op.insert("// entry\n".into()).retain("fn main() {}".len());
+        op
+    }));
+    client2.push_operation({
+        let mut op = Operation::nop(&client2.current_content().unwrap());
+        op.put(anchor_id("bookmark"), Anchor::new(0, Bias::Right));
+        op
+    });
+    {
+        let response1 = client1.send_to_server().unwrap();
+        let response2 = client2.send_to_server().unwrap();
+        client1.apply_response(block_on(response1).unwrap()).unwrap();
+        client2.apply_response(block_on(response2).unwrap()).unwrap();
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+
+    let expected = Target {
+        base: "// entry\nfn main() {}".into(),
+        anchors: {
+            let mut anchors = BTreeMap::new();
+            anchors.insert(
+                anchor_id("breakpoint"),
+                Anchor::new("// entry\nfn main() {".len(), Bias::Right),
+            );
+            anchors.insert(anchor_id("bookmark"), Anchor::new("// entry\n".len(), Bias::Right));
+            anchors
+        },
+    };
+    assert_eq!(client1.current_content().unwrap(), expected);
+    assert_eq!(client2.current_content().unwrap(), expected);
+    assert_eq!(server.borrow().current_state().content, expected);
+}
@@ -0,0 +1,154 @@
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::attribution::*;
+use ot::server::*;
+use ot::client::*;
+use ot::client::Connection;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+extern crate futures;
+use futures::executor::block_on;
+
+#[test]
+fn test_charwise_attribution() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    let mut attribution = Attribution::new(&server.borrow());
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+    server
+        .borrow_mut()
+        .set_author(connection1.client_id(), "alice".into());
+    server
+        .borrow_mut()
+        .set_author(connection2.client_id(), "bob".into());
+
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.insert("hello world".into());
+        op
+    });
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+    assert_eq!(
+        server.borrow().current_state().metadata.session,
+        Some(connection1.client_id())
+    );
+
+    // the second user replaces a word
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.retain("hello ".len())
+            .delete("world".len())
+            .insert("世界".into());
+        op
+    });
+    {
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_response(patch).unwrap();
+    }
+
+    // the server is modified directly
+    server
+        .borrow_mut()
+        .modify(Id(2), {
+            let mut op = Operation::new();
+            op.retain("hello 世界".len()).insert("!".into());
+            op
+        })
+        .unwrap();
+
+    attribution.catch_up(&server.borrow());
+    assert_eq!(attribution.id(), &Id(3));
+    assert_eq!(
+        attribution.spans(),
+        &[
+            Span {
+                len: "hello ".len(),
+                author: Some("alice".into()),
+                id: Id(1),
+            },
+            Span {
+                len: "世界".len(),
+                author: Some("bob".into()),
+                id: Id(2),
+            },
+            Span {
+                len: "!".len(),
+                author: None,
+                id: Id(3),
+            },
+        ][..]
+    );
+    assert_eq!(
+        attribution.span_at("hello ".len()).unwrap().author,
+        Some("bob".into())
+    );
+    assert!(attribution.span_at("hello 世界!".len()).is_none());
+
+    // catching up again does nothing
+    attribution.catch_up(&server.borrow());
+    assert_eq!(attribution.spans().len(), 3);
+}
+
+#[test]
+fn test_charwise_attribution_fork() {
+    let mut server = Server::new();
+    server
+        .modify_with(
+            Metadata {
+                author: Some("alice".into()),
+                ..Metadata::default()
+            },
+            Id(0),
+            {
+                let mut op = Operation::new();
+                op.insert("hello".into());
+                op
+            },
+        )
+        .unwrap();
+
+    // a fork keeps the authorship of the content it starts with
+    let mut fork = server.fork(&Id(1)).unwrap();
+    fork.modify_with(
+        Metadata {
+            author: Some("bob".into()),
+            ..Metadata::default()
+        },
+        Id(1),
+        {
+            let mut op = Operation::new();
+            op.retain("hello".len()).insert(" world".into());
+            op
+        },
+    ).unwrap();
+
+    let mut attribution = Attribution::new(&fork);
+    attribution.catch_up(&fork);
+    assert_eq!(
+        attribution.spans(),
+        &[
+            Span {
+                len: "hello".len(),
+                author: Some("alice".into()),
+                id: Id(1),
+            },
+            Span {
+                len: " world".len(),
+                author: Some("bob".into()),
+                id: Id(2),
+            },
+        ][..]
+    );
+}
@@ -0,0 +1,364 @@
+extern crate ot;
+
+use ot::comment::*;
+use ot::charwise::Operation as BaseOperation;
+use ot::Operation as OperationTrait;
+use ot::cs::*;
+use ot::server::*;
+use ot::client::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+mod util;
+use util::thread_id;
+use util::charwise::random_range;
+
+extern crate rand;
+use rand::Rng;
+
+extern crate futures;
+use futures::executor::block_on;
+
+fn comment_id(s: &str) -> CommentId {
+    CommentId(s.into())
+}
+
+fn comment(author: &str, body: &str) -> Comment {
+    Comment {
+        author: author.into(),
+        body: body.into(),
+    }
+}
+
+fn random_target<R: Rng>(rng: &mut R, thread_num: usize, len: usize) -> Target<BaseOperation> {
+    let base = util::charwise::random_string(rng, len);
+    let threads = (0..thread_num)
+        .map(|i| {
+            let (start, end) = random_range(rng, &base);
+            let thread = Thread {
+                range: Range::new(start, end),
+                resolved: rng.gen(),
+                comments: BTreeMap::new(),
+            };
+            (thread_id(&i.to_string()), thread)
+        })
+        .collect();
+    Target { base, threads }
+}
+
+fn random_operation<R: Rng>(
+    rng: &mut R,
+    thread_num: usize,
+    target: &Target<BaseOperation>,
+) -> Operation<BaseOperation> {
+    let base = util::charwise::random_operation(rng, &target.base);
+    let content = base.apply(&target.base);
+    let mut op = target.operate(base);
+    for _ in 0..rng.gen_range(0, 5) {
+        let id = thread_id(&rng.gen_range(0, thread_num).to_string());
+        match rng.gen_range(0, 5) {
+            0 => {
+                let (start, end) = random_range(rng, &content);
+                op.open(id, start, end);
+            }
+            1 => {
+                op.resolve(id, rng.gen());
+            }
+            2 => {
+                let body = util::charwise::random_string(rng, 5);
+                op.comment(id, comment_id(&rng.gen_range(0, 5).to_string()), comment("", &body));
+            }
+            3 => {
+                op.delete_comment(id, comment_id(&rng.gen_range(0, 5).to_string()));
+            }
+            4 => {
+                op.delete_thread(id);
+            }
+            _ => unreachable!(),
+        }
+    }
+    op
+}
+
+#[test]
+fn test_apply() {
+    let target = Target {
+        base: "hello world".into(),
+        threads: BTreeMap::new(),
+    };
+
+    let mut op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello world".len());
+        op
+    });
+    op.open(thread_id("greeting"), 0, "hello".len())
+        .open(thread_id("noun"), "hello ".len(), "hello world".len())
+        .comment(
+            thread_id("greeting"),
+            comment_id("0"),
+            comment("alice", "too casual"),
+        );
+    let target = op.apply(&target);
+
+    // text inserted at the ends is outside of the ranges
+    // partly deleted range shrinks, and fully deleted one is orphaned
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.insert("oh, ".into())
+            .delete("he".len())
+            .retain("llo".len())
+            .insert("!".into())
+            .retain(" ".len())
+            .delete("world".len());
+        op
+    });
+    let target = op.apply(&target);
+
+    assert_eq!(target.base, "oh, llo! ");
+    assert_eq!(
+        target.threads[&thread_id("greeting")],
+        Thread {
+            range: Range::new("oh, ".len(), "oh, llo".len()),
+            resolved: false,
+            comments: {
+                let mut comments = BTreeMap::new();
+                comments.insert(comment_id("0"), comment("alice", "too casual"));
+                comments
+            },
+        }
+    );
+    assert_eq!(
+        target.threads[&thread_id("noun")].range,
+        Range {
+            start: "oh, llo! ".len(),
+            end: "oh, llo! ".len(),
+            orphaned: true,
+        }
+    );
+    assert_eq!(target.orphaned(), vec![&thread_id("noun")]);
+
+    // an orphaned thread stays orphaned until it is moved
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("oh, llo! ".len()).insert("world".into());
+        op
+    });
+    let target = op.apply(&target);
+    assert!(target.threads[&thread_id("noun")].range.orphaned);
+
+    let mut op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("oh, llo! world".len());
+        op
+    });
+    op.open(thread_id("noun"), "oh, llo! ".len(), "oh, llo! world".len());
+    let target = op.apply(&target);
+    assert!(target.orphaned().is_empty());
+}
+
+#[test]
+fn test_empty_range() {
+    let target: Target<BaseOperation> = Target {
+        base: "hello world".into(),
+        threads: BTreeMap::new(),
+    };
+
+    // a reversed range is turned around, and an empty one is a note at the position
+    let mut op = Operation::nop(&target);
+    op.open(thread_id("reversed"), "hello".len(), 0)
+        .open(thread_id("note"), "hello".len(), "hello".len());
+    let target = op.apply(&target);
+    assert_eq!(
+        target.threads[&thread_id("reversed")].range,
+        Range::new(0, "hello".len())
+    );
+
+    // the note is not orphaned by edits, and text inserted at it goes after it
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello".len())
+            .insert(",".into())
+            .retain(" world".len());
+        op
+    });
+    let target = op.apply(&target);
+    assert_eq!(
+        target.threads[&thread_id("note")].range,
+        Range::new("hello".len(), "hello".len())
+    );
+    assert!(target.orphaned().is_empty());
+}
+
+#[test]
+fn test_delete_thread() {
+    let target = {
+        let target: Target<BaseOperation> = Target {
+            base: "hello".into(),
+            threads: BTreeMap::new(),
+        };
+        let mut op = Operation::nop(&target);
+        op.open(thread_id("0"), 0, "hello".len())
+            .comment(thread_id("0"), comment_id("0"), comment("alice", "typo?"));
+        op.apply(&target)
+    };
+
+    // deleting wins over a concurrent reply
+    let mut left = Operation::nop(&target);
+    left.delete_thread(thread_id("0"));
+    let mut right = Operation::nop(&target);
+    right
+        .comment(thread_id("0"), comment_id("1"), comment("bob", "no"))
+        .open(thread_id("0"), 1, 2);
+
+    let (left_, right_) = left.clone().transform(right.clone());
+    let left = left.compose(right_).apply(&target);
+    let right = right.compose(left_).apply(&target);
+    assert_eq!(left, right);
+    assert!(left.threads.is_empty());
+
+    // a thread opened again after deleting is a new one
+    let mut op = Operation::nop(&target);
+    op.delete_thread(thread_id("0")).open(thread_id("0"), 0, 1);
+    let applied = op.apply(&target);
+    assert_eq!(applied.threads[&thread_id("0")].range, Range::new(0, 1));
+    assert!(applied.threads[&thread_id("0")].comments.is_empty());
+}
+
+#[test]
+fn test_transform() {
+    let target = {
+        let target = Target {
+            base: "hello".into(),
+            threads: BTreeMap::new(),
+        };
+        let mut op = Operation::nop(&target);
+        op.open(thread_id("0"), 0, "hello".len());
+        op.apply(&target)
+    };
+
+    // both users reply and resolve at the same time
+    let mut left = Operation::nop(&target);
+    left.comment(thread_id("0"), comment_id("alice-0"), comment("alice", "done"))
+        .resolve(thread_id("0"), true);
+    let mut right = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello".len()).insert(" world".into());
+        op
+    });
+    right
+        .comment(thread_id("0"), comment_id("bob-0"), comment("bob", "not yet"))
+        .resolve(thread_id("0"), false);
+
+    let (left_, right_) = left.clone().transform(right.clone());
+    let left = left.compose(right_).apply(&target);
+    let right = right.compose(left_).apply(&target);
+    assert_eq!(left, right);
+
+    // both replies are kept, and the left one wins the resolution
+    let thread = &left.threads[&thread_id("0")];
+    assert_eq!(thread.range, Range::new(0, "hello".len()));
+    assert!(thread.resolved);
+    assert_eq!(
+        thread.comments.keys().collect::<Vec<_>>(),
+        vec![&comment_id("alice-0"), &comment_id("bob-0")]
+    );
+}
+
+#[test]
+fn fuzz_test_compose() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..100 {
+        let len = rng.gen_range(32, 100);
+        let thread_num = rng.gen_range(1, 10);
+        let target = random_target(&mut rng, thread_num, len);
+
+        let first = random_operation(&mut rng, thread_num, &target);
+        let applied = first.apply(&target);
+        let second = random_operation(&mut rng, thread_num, &applied);
+
+        let double_applied = second.apply(&applied);
+        let compose_applied = first.compose(second).apply(&target);
+
+        assert_eq!(double_applied, compose_applied);
+    }
+}
+
+#[test]
+fn fuzz_test_transform() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(32, 100);
+        let thread_num = rng.gen_range(1, 10);
+        let target = random_target(&mut rng, thread_num, len);
+
+        let left = random_operation(&mut rng, thread_num, &target);
+        let right = random_operation(&mut rng, thread_num, &target);
+
+        let (left_, right_) = left.clone().transform(right.clone());
+
+        let left = left.compose(right_);
+        let right = right.compose(left_);
+
+        assert_eq!(left.apply(&target), right.apply(&target));
+    }
+}
+
+#[test]
+fn test_charwise_comment_client_server() {
+    let server: Rc<RefCell<Server<Operation<BaseOperation>>>> =
+        Rc::new(RefCell::new(Server::new()));
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    client1.push_operation({
+        let mut op = Operation::with_content({
+            let mut op = BaseOperation::new();
+            op.insert("hello world".into());
+            op
+        });
+        op.open(thread_id("0"), "hello ".len(), "hello world".len())
+            .comment(thread_id("0"), comment_id("0"), comment("alice", "who?"));
+        op
+    });
+    {
+        let response = client1.send_to_server().unwrap();
+        client1.apply_response(block_on(response).unwrap()).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+
+    // one user deletes the commented text while the other replies
+    client1.push_operation(client1.current_content().unwrap().operate({
+        let mut op = BaseOperation::new();
+        op.retain("hello".len()).delete(" world".len());
+        op
+    }));
+    client2.push_operation({
+        let mut op = Operation::nop(&client2.current_content().unwrap());
+        op.comment(thread_id("0"), comment_id("1"), comment("bob", "everyone"));
+        op
+    });
+    {
+        let response1 = client1.send_to_server().unwrap();
+        let response2 = client2.send_to_server().unwrap();
+        client1.apply_response(block_on(response1).unwrap()).unwrap();
+        client2.apply_response(block_on(response2).unwrap()).unwrap();
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+
+    let content = server.borrow().current_state().content.clone();
+    assert_eq!(content.base, "hello");
+    assert_eq!(content.orphaned(), vec![&thread_id("0")]);
+    assert_eq!(content.threads[&thread_id("0")].comments.len(), 2);
+    assert_eq!(client1.current_content().unwrap(), content);
+    assert_eq!(client2.current_content().unwrap(), content);
+}
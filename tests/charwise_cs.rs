@@ -6,12 +6,21 @@ use ot::server::*;
 use ot::client::*;
 
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
 
 extern crate failure;
+extern crate rand;
+extern crate serde_json;
 
 extern crate futures;
 use futures::executor::block_on;
+use futures::{FutureExt, StreamExt};
+
+mod util;
+use util::flaky_connection::FlakyConnection;
+use util::charwise::{random_operation, random_string};
 
 #[test]
 fn test_charwise_client_server() {
@@ -35,8 +44,8 @@ fn test_charwise_client_server() {
         op
     });
     {
-        let (id, op) = block_on(client1.send_to_server().unwrap()).unwrap();
-        client1.apply_patch(id, op).unwrap();
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_patch(patch).unwrap();
     }
 
     assert_eq!(client1.current_content().unwrap(), "こんにちは 世界");
@@ -48,8 +57,8 @@ fn test_charwise_client_server() {
         op
     });
     {
-        let (id, op) = block_on(client2.send_to_server().unwrap()).unwrap();
-        client2.apply_patch(id, op).unwrap();
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_patch(patch).unwrap();
     }
 
     assert_eq!(client1.current_content().unwrap(), "こんにちは 世界");
@@ -66,8 +75,8 @@ fn test_charwise_client_server() {
         op
     });
     {
-        let (id, op) = block_on(client1.send_to_server().unwrap()).unwrap();
-        client1.apply_patch(id, op).unwrap();
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -80,8 +89,8 @@ fn test_charwise_client_server() {
     );
 
     {
-        let (latest_id, diff) = block_on(client2.send_get_patch()).unwrap();
-        client2.apply_patch(latest_id, diff).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -93,3 +102,1224 @@ fn test_charwise_client_server() {
         "!さようなら 世界"
     );
 }
+
+#[test]
+fn test_charwise_resend() {
+    let server = Rc::new(RefCell::new(Server::new()));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut client = block_on(Client::with_connection(&connection)).unwrap();
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+
+    // the response of the first send is lost
+    let _ = client.send_to_server().unwrap();
+    {
+        let patch = block_on(client.resend().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+
+    assert_eq!(client.current_content().unwrap(), "こんにちは");
+    assert_eq!(server.borrow().current_state().id, Id(1));
+    assert_eq!(server.borrow().current_state().content, "こんにちは");
+    assert!(client.resend().is_err());
+}
+
+#[test]
+fn test_charwise_duplicate_submission() {
+    let mut server = Server::new();
+    let client_id = server.new_client_id();
+
+    let submission = |seq, diff| Submission {
+        client_id: client_id.clone(),
+        seq: seq,
+        parent: Id(0),
+        diff: diff,
+        acknowledged: None,
+    };
+    let insert = |s: &str| {
+        let mut op = Operation::new();
+        op.insert(s.into());
+        op
+    };
+
+    let patch = server.submit(submission(1, insert("世界"))).unwrap();
+    assert_eq!(patch.id, Id(1));
+
+    // a concurrent modification by another client
+    server.modify(Id(0), insert("!")).unwrap();
+
+    let patch = server.submit(submission(1, insert("世界"))).unwrap();
+    assert_eq!(patch.id, Id(1));
+    assert_eq!(patch.diff.target_len(), "世界".len());
+    assert_eq!(server.current_state().content, "!世界");
+
+    assert!(server.submit(submission(0, insert("世界"))).is_err());
+    assert!(server.submit(submission(3, insert("世界"))).is_err());
+
+    let patch = server
+        .submit(Submission {
+            parent: Id(2),
+            ..submission(2, {
+                let mut op = Operation::new();
+                op.insert("こんにちは".into()).retain("!世界".len());
+                op
+            })
+        })
+        .unwrap();
+    assert_eq!(patch.id, Id(3));
+    assert_eq!(server.current_state().content, "こんにちは!世界");
+
+    // servers persisted before sessions were introduced can be loaded
+    let mut json = serde_json::to_value(&server).unwrap();
+    {
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("next_client_id");
+        fields.remove("sessions");
+    }
+    let server: Server<Operation> = serde_json::from_value(json).unwrap();
+    assert_eq!(server.current_state().content, "こんにちは!世界");
+}
+
+#[test]
+fn test_charwise_metadata() {
+    let mut server = Server::new();
+    let client_id = server.new_client_id();
+    server.set_author(client_id.clone(), "alice".into());
+
+    let patch = server
+        .submit(Submission {
+            client_id: client_id.clone(),
+            seq: 1,
+            parent: Id(0),
+            diff: {
+                let mut op = Operation::new();
+                op.insert("hello".into());
+                op
+            },
+            acknowledged: None,
+        })
+        .unwrap();
+    assert_eq!(patch.metadata.len(), 1);
+    assert_eq!(patch.metadata[0].author, Some("alice".into()));
+    assert_eq!(patch.metadata[0].session, Some(client_id.clone()));
+    assert!(patch.metadata[0].timestamp > 0);
+
+    // the server is modified directly with its own fields
+    server
+        .modify_with(
+            Metadata {
+                author: Some("bot".into()),
+                fields: {
+                    let mut fields = BTreeMap::new();
+                    fields.insert("reason".into(), "greeting".into());
+                    fields
+                },
+                ..Metadata::default()
+            },
+            Id(1),
+            {
+                let mut op = Operation::new();
+                op.retain("hello".len()).insert(" world".into());
+                op
+            },
+        )
+        .unwrap();
+
+    // patches have the metadata of the states they cover
+    let patch = server.get_patch(&Id(0)).unwrap();
+    let authors: Vec<_> = patch
+        .metadata
+        .iter()
+        .map(|metadata| metadata.author.clone().unwrap())
+        .collect();
+    assert_eq!(authors, vec!["alice", "bot"]);
+    assert_eq!(patch.metadata[1].session, None);
+    assert!(patch.metadata[0].timestamp <= patch.metadata[1].timestamp);
+
+    // and they are persisted with the history
+    let json = serde_json::to_string(&server).unwrap();
+    let server: Server<Operation> = serde_json::from_str(&json).unwrap();
+    assert_eq!(server.current_state().metadata.fields["reason"], "greeting");
+    assert_eq!(server.get_patch(&Id(0)).unwrap().metadata.len(), 2);
+}
+
+#[test]
+fn test_charwise_metadata_limit() {
+    let mut server = Server::<Operation>::new();
+    for i in 0..5 {
+        let content = server.current_state().content.clone();
+        server
+            .modify_with(
+                Metadata {
+                    author: Some(i.to_string()),
+                    ..Metadata::default()
+                },
+                Id(i),
+                {
+                    let mut op = Operation::new();
+                    op.retain(content.len()).insert(i.to_string());
+                    op
+                },
+            )
+            .unwrap();
+    }
+    assert_eq!(server.get_patch(&Id(0)).unwrap().metadata.len(), 5);
+
+    // only the newest states are included
+    server.set_patch_metadata_limit(2);
+    let authors = |metadata: Vec<Metadata>| -> Vec<String> {
+        metadata
+            .into_iter()
+            .map(|metadata| metadata.author.unwrap())
+            .collect()
+    };
+    let patch = server.get_patch(&Id(0)).unwrap();
+    assert_eq!(patch.id, Id(5));
+    assert_eq!(authors(patch.metadata), vec!["3", "4"]);
+    let patch = server.patch_between(&Id(5), &Id(0)).unwrap();
+    assert_eq!(authors(patch.metadata), vec!["4", "3"]);
+
+    server.set_patch_metadata_limit(0);
+    assert!(server.get_patch(&Id(0)).unwrap().metadata.is_empty());
+}
+
+#[test]
+fn test_charwise_time_travel() {
+    use ot::Operation as OperationTrait;
+
+    let mut server = Server::<Operation>::new();
+    for (i, word) in ["hello", " world", "!"].iter().enumerate() {
+        let content = server.current_state().content.clone();
+        server
+            .modify(Id(i), {
+                let mut op = Operation::new();
+                op.retain(content.len()).insert(word.to_string());
+                op
+            })
+            .unwrap();
+    }
+    server
+        .modify(Id(3), {
+            let mut op = Operation::new();
+            op.delete("hello".len())
+                .insert("goodbye".into())
+                .retain(" world!".len());
+            op
+        })
+        .unwrap();
+
+    assert_eq!(server.state_at(&Id(2)).unwrap().content, "hello world");
+    assert!(server.state_at(&Id(5)).is_err());
+
+    // forward
+    let patch = server.patch_between(&Id(1), &Id(3)).unwrap();
+    assert_eq!(patch.id, Id(3));
+    assert_eq!(patch.metadata.len(), 2);
+    assert_eq!(patch.diff.apply(&"hello".into()), "hello world!");
+
+    // backward
+    let patch = server.patch_between(&Id(4), &Id(1)).unwrap();
+    assert_eq!(patch.id, Id(1));
+    assert_eq!(patch.metadata.len(), 3);
+    assert_eq!(patch.checksum, server.state_at(&Id(1)).unwrap().checksum);
+    assert_eq!(patch.diff.apply(&"goodbye world!".into()), "hello");
+
+    // identity
+    let server = Server::<Operation>::new();
+    let patch = server.patch_between(&Id(0), &Id(0)).unwrap();
+    assert_eq!(patch.diff.apply(&"".into()), "");
+    assert!(patch.metadata.is_empty());
+}
+
+#[test]
+fn test_charwise_fork() {
+    let mut server = Server::new();
+    server
+        .modify_with(
+            Metadata {
+                author: Some("alice".into()),
+                ..Metadata::default()
+            },
+            Id(0),
+            {
+                let mut op = Operation::new();
+                op.insert("hello world".into());
+                op
+            },
+        )
+        .unwrap();
+
+    // the fork shares the history up to the fork point
+    let mut fork = server.fork(&Id(1)).unwrap();
+    assert_eq!(fork.fork_point(), Some(&Id(1)));
+    assert_eq!(fork.current_state().id, Id(1));
+    assert_eq!(fork.current_state().content, "hello world");
+    assert_eq!(fork.current_state().metadata.author, Some("alice".into()));
+    assert!(server.fork(&Id(2)).is_err());
+
+    // both evolve independently
+    fork
+        .modify(Id(1), {
+            let mut op = Operation::new();
+            op.retain("hello world".len()).insert("!".into());
+            op
+        })
+        .unwrap();
+    fork
+        .modify(Id(2), {
+            let mut op = Operation::new();
+            op.delete("hello".len())
+                .insert("goodbye".into())
+                .retain(" world!".len());
+            op
+        })
+        .unwrap();
+    server
+        .modify(Id(1), {
+            let mut op = Operation::new();
+            op.insert("oh, ".into()).retain("hello world".len());
+            op
+        })
+        .unwrap();
+    assert_eq!(fork.current_state().content, "goodbye world!");
+    assert_eq!(server.current_state().content, "oh, hello world");
+
+    // merge the fork back
+    let patch = server
+        .merge(
+            fork,
+            Metadata {
+                author: Some("drafts".into()),
+                ..Metadata::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(patch.id, Id(3));
+    assert_eq!(server.current_state().content, "oh, goodbye world!");
+    assert_eq!(server.current_state().metadata.author, Some("drafts".into()));
+
+    // only forks can be merged
+    assert!(server.merge(Server::new(), Metadata::default()).is_err());
+
+    // forks of another document can not be merged, even if the content is the same
+    let mut other = Server::new();
+    other
+        .modify(Id(0), {
+            let mut op = Operation::new();
+            op.insert("hello world".into());
+            op
+        })
+        .unwrap();
+    let fork = other.fork(&Id(1)).unwrap();
+    assert!(server.merge(fork, Metadata::default()).is_err());
+
+    // a persisted document keeps its forks
+    let fork = server.fork(&Id(3)).unwrap();
+    let json = serde_json::to_string(&server).unwrap();
+    let mut server: Server<Operation> = serde_json::from_str(&json).unwrap();
+    let json = serde_json::to_string(&fork).unwrap();
+    let fork: Server<Operation> = serde_json::from_str(&json).unwrap();
+    assert_eq!(server.merge(fork, Metadata::default()).unwrap().id, Id(4));
+
+    // a fork is merged only once, even after the document is persisted again
+    let json = serde_json::to_string(&server).unwrap();
+    let mut server: Server<Operation> = serde_json::from_str(&json).unwrap();
+    let mut fork = server.fork(&Id(4)).unwrap();
+    fork
+        .modify(Id(4), {
+            let mut op = Operation::new();
+            op.retain("oh, goodbye world!".len()).insert("!".into());
+            op
+        })
+        .unwrap();
+    let json = serde_json::to_string(&fork).unwrap();
+    let copy: Server<Operation> = serde_json::from_str(&json).unwrap();
+    server.merge(fork, Metadata::default()).unwrap();
+    assert_eq!(server.current_state().content, "oh, goodbye world!!");
+    assert!(server.merge(copy, Metadata::default()).is_err());
+    assert_eq!(server.current_state().id, Id(5));
+    assert_eq!(server.current_state().content, "oh, goodbye world!!");
+}
+
+#[test]
+fn test_charwise_validation() {
+    use ot::Operation as OperationTrait;
+    use ot::client::Connection;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .add_validator(|state: &State<Operation>, op: &Operation, _: &Metadata| {
+            if op.apply(&state.content).len() > 20 {
+                Err(Rejection::new("too-large", "the document is too large"))
+            } else {
+                Ok(())
+            }
+        });
+    server
+        .borrow_mut()
+        .add_validator(|state: &State<Operation>, op: &Operation, _: &Metadata| {
+            if op.apply(&state.content).contains('#') {
+                Err(Rejection::new("forbidden", "# is not allowed"))
+            } else {
+                Ok(())
+            }
+        });
+    server
+        .borrow_mut()
+        .add_validator(|_: &State<Operation>, _: &Operation, metadata: &Metadata| {
+            if metadata.author == Some("guest".into()) {
+                Err(Rejection::new("read-only", "guests can not edit"))
+            } else {
+                Ok(())
+            }
+        });
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+    server
+        .borrow_mut()
+        .set_author(connection2.client_id(), "guest".into());
+
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.insert("hello world".into());
+        op
+    });
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+    }
+
+    // an invalid operation is sent on an old state, and another is made while it is in flight
+    server
+        .borrow_mut()
+        .modify(Id(1), {
+            let mut op = Operation::new();
+            op.insert("oh, ".into()).retain("hello world".len());
+            op
+        })
+        .unwrap();
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.retain("hello".len()).insert("#".into()).retain(" world".len());
+        op
+    });
+    let response = client1.send_to_server().unwrap();
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.retain("hello# world".len()).insert("!".into());
+        op
+    });
+
+    // the rejected operation is rolled back, keeping the later one
+    match client1.apply_response(block_on(response).unwrap()) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "forbidden"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client1.current_content().unwrap(), "oh, hello world");
+    assert_eq!(client1.unsynced_content().unwrap(), "oh, hello world!");
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+    }
+    assert_eq!(server.borrow().current_state().content, "oh, hello world!");
+
+    // per-user permissions
+    let patch = block_on(client2.send_get_patch()).unwrap();
+    client2.apply_patch(patch).unwrap();
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.delete("oh, ".len()).retain("hello world!".len());
+        op
+    });
+    let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+    match client2.apply_response(patch) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "read-only"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client2.unsynced_content().unwrap(), "oh, hello world!");
+
+    // a response applied as a patch is handled in the same way
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.retain("oh, hello world!".len()).insert("?".into());
+        op
+    });
+    let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+    match client2.apply_patch(patch.clone()) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "read-only"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client2.unsynced_content().unwrap(), "oh, hello world!");
+    match client2.apply_response(patch) {
+        Err(ClientError::NotWaiting) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client2.current_content().unwrap(), "oh, hello world!");
+
+    // direct modifications are validated as well
+    let result = server.borrow_mut().modify(Id(3), {
+        let mut op = Operation::new();
+        op.retain("oh, hello world!".len())
+            .insert(" and goodbye".into());
+        op
+    });
+    assert!(result.is_err());
+    assert_eq!(server.borrow().current_state().id, Id(3));
+}
+
+#[test]
+fn test_charwise_anchor_rejection() {
+    use ot::Operation as OperationTrait;
+    use ot::anchor::{Anchor, Operation as AnchorOperation, Target};
+    use ot::cs::pipelined_client::PipelinedClient;
+    use ot::selection::Bias;
+    use util::anchor_id;
+
+    let server: Rc<RefCell<Server<AnchorOperation<Operation>>>> =
+        Rc::new(RefCell::new(Server::new()));
+    server.borrow_mut().add_validator(
+        |_: &State<AnchorOperation<Operation>>, op: &AnchorOperation<Operation>, _: &Metadata| {
+            if op.changed.contains(&anchor_id("forbidden")) {
+                Err(Rejection::new("forbidden", "the anchor is reserved"))
+            } else {
+                Ok(())
+            }
+        },
+    );
+    let forbidden = |target: &Target<Operation>| {
+        let mut op = AnchorOperation::nop(target);
+        op.put(anchor_id("forbidden"), Anchor::new(0, Bias::Left));
+        op
+    };
+    let bookmark = |target: &Target<Operation>| {
+        let mut op = AnchorOperation::nop(target);
+        op.put(anchor_id("bookmark"), Anchor::new(0, Bias::Left));
+        op
+    };
+
+    // the server can not undo anchor operations, so the client drops the rejected one
+    // along with the one built on it
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut client = block_on(Client::with_connection(&connection)).unwrap();
+    let events = client.subscribe();
+    client.push_operation(forbidden(&client.current_content().unwrap()));
+    let response = client.send_to_server().unwrap();
+    client.push_operation(bookmark(&client.unsynced_content().unwrap()));
+    let patch = block_on(response).unwrap();
+    assert!(patch.from_parent);
+    match client.apply_response(patch) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "forbidden"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client.current_content().unwrap(), server.borrow().current_state().content);
+    assert!(client.unsynced_content().unwrap().anchors.is_empty());
+    drop(client);
+    let events = block_on(events.collect::<Vec<_>>()).unwrap();
+    match events[1] {
+        ClientEvent::Reset => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+
+    // the server refuses the operations in flight built on it, and their seq are used again
+    let mut client = block_on(PipelinedClient::with_connection(
+        mock_connection::MockConnection::new(server.clone()),
+        2,
+    )).unwrap();
+    client.push_operation(forbidden(&client.current_content()));
+    let response1 = client.send_to_server().unwrap();
+    client.push_operation(bookmark(&client.unsynced_content()));
+    let response2 = client.send_to_server().unwrap();
+    match client.apply_response(block_on(response1).unwrap()) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "forbidden"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(block_on(response2).is_err());
+    assert_eq!(client.in_flight(), 0);
+
+    client.push_operation(bookmark(&client.current_content()));
+    let patch = block_on(client.send_to_server().unwrap()).unwrap();
+    client.apply_response(patch).unwrap();
+    assert_eq!(client.current_content(), server.borrow().current_state().content);
+    assert!(client.current_content().anchors.contains_key(&anchor_id("bookmark")));
+}
+
+#[test]
+fn test_charwise_reconnect() {
+    use ot::client::Connection;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+    let online = Rc::new(Cell::new(true));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let mut client = block_on(Client::with_connection(FlakyConnection::new(
+        server.clone(),
+        online.clone(),
+    ))).unwrap();
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+    online.set(false);
+    assert!(block_on(client.send_to_server().unwrap()).is_err());
+
+    // editing continues while the connection is down
+    client.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは".len()).insert(" 世界".into());
+        op
+    });
+    other.push_operation({
+        let mut op = Operation::new();
+        op.insert("!".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+
+    online.set(true);
+    let client_id = {
+        let connection = FlakyConnection::new(server.clone(), online.clone());
+        let client_id = connection.client_id();
+        let patch = block_on(client.reconnect(connection).unwrap()).unwrap();
+        client.apply_patch(patch).unwrap();
+        client_id
+    };
+
+    assert_eq!(client.current_content().unwrap(), "こんにちは!");
+    assert_eq!(client.unsynced_content().unwrap(), "こんにちは 世界!");
+
+    // the response is lost this time, but the operation reached the server
+    let _ = client.send_to_server().unwrap();
+    {
+        let connection = FlakyConnection::new(server.clone(), online.clone());
+        let patch = block_on(client.reconnect(connection).unwrap()).unwrap();
+        client.apply_patch(patch).unwrap();
+    }
+    {
+        let patch = block_on(other.send_get_patch()).unwrap();
+        other.apply_patch(patch).unwrap();
+    }
+
+    assert_eq!(server.borrow().current_state().id, Id(3));
+    assert_eq!(client.current_content().unwrap(), "こんにちは 世界!");
+    assert_eq!(other.current_content().unwrap(), "こんにちは 世界!");
+
+    // operations after reconnecting are sent with the id of the new connection
+    assert_eq!(
+        server.borrow().current_state().metadata.session,
+        Some(client_id)
+    );
+}
+
+#[test]
+fn test_charwise_reconnect_from_error() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .modify(Id(0), {
+            let mut op = Operation::new();
+            op.insert("hello".into());
+            op
+        })
+        .unwrap();
+
+    let mut client: Client<Operation, _> = Client::Error("broken".into());
+    {
+        let connection = mock_connection::MockConnection::new(server.clone());
+        let patch = block_on(client.reconnect(connection).unwrap()).unwrap();
+        client.apply_patch(patch).unwrap();
+    }
+    assert_eq!(client.current_content().unwrap(), "hello");
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.retain("hello".len()).insert(" world".into());
+        op
+    });
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+    assert_eq!(server.borrow().current_state().content, "hello world");
+}
+
+#[test]
+fn test_charwise_offline_editing() {
+    let mut rng = rand::thread_rng();
+
+    let server = Rc::new(RefCell::new(Server::new()));
+    let online = Rc::new(Cell::new(true));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let mut client = block_on(Client::with_connection(FlakyConnection::new(
+        server.clone(),
+        online.clone(),
+    ))).unwrap();
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは 世界".into());
+        op
+    });
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+    {
+        let patch = block_on(other.send_get_patch()).unwrap();
+        other.apply_patch(patch).unwrap();
+    }
+
+    // the first send of the offline session fails, and the rest stays in the buffer
+    online.set(false);
+    for i in 0..50 {
+        let content = client.unsynced_content().unwrap();
+        client.push_operation(random_operation(&mut rng, &content));
+        if i == 0 {
+            assert!(block_on(client.send_to_server().unwrap()).is_err());
+        }
+    }
+    let expected = client.unsynced_content().unwrap();
+
+    // stored as it would be on disk
+    let mut stored = vec![];
+    serde_json::to_writer(&mut stored, &client.snapshot().unwrap()).unwrap();
+    drop(client);
+
+    // the server keeps advancing meanwhile
+    for _ in 0..50 {
+        let content = other.current_content().unwrap();
+        other.push_operation(random_operation(&mut rng, &content));
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+
+    online.set(true);
+    let snapshot: ClientSnapshot<Operation> =
+        serde_json::from_reader(&stored[..]).unwrap();
+    let mut client = Client::restore(
+        snapshot,
+        FlakyConnection::new(server.clone(), online.clone()),
+    );
+    assert_eq!(client.unsynced_content().unwrap(), expected);
+
+    {
+        let patch = block_on(client.resync()).unwrap();
+        client.apply_patch(patch).unwrap();
+    }
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+    {
+        let patch = block_on(other.send_get_patch()).unwrap();
+        other.apply_patch(patch).unwrap();
+    }
+
+    let content = server.borrow().current_state().content.clone();
+    assert_eq!(client.current_content().unwrap(), content);
+    assert_eq!(other.current_content().unwrap(), content);
+    assert_eq!(
+        client.current_content().unwrap().len(),
+        server.borrow().current_state().diff.target_len()
+    );
+}
+
+#[test]
+fn test_charwise_events() {
+    use ot::Operation as OperationTrait;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+    let online = Rc::new(Cell::new(true));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let mut client = block_on(Client::with_connection(FlakyConnection::new(
+        server.clone(),
+        online.clone(),
+    ))).unwrap();
+    let events = client.subscribe();
+
+    other.push_operation({
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは".len())
+            .insert(" ".into())
+            .retain("世界".len());
+        op
+    });
+    let view = client.unsynced_content().unwrap();
+
+    {
+        let patch = block_on(other.send_get_patch()).unwrap();
+        other.apply_patch(patch).unwrap();
+    }
+    other.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは世界".len()).insert("!".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+    {
+        let patch = block_on(client.send_get_patch()).unwrap();
+        client.apply_patch(patch).unwrap();
+    }
+
+    online.set(false);
+    assert!(block_on(client.send_get_patch()).is_err());
+
+    let unsynced = client.unsynced_content().unwrap();
+    assert_eq!(unsynced, "こんにちは 世界!");
+    drop(client);
+
+    let events = block_on(events.collect::<Vec<_>>()).unwrap();
+    assert_eq!(events.len(), 5);
+    match events[0] {
+        ClientEvent::WaitingForResponse => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[1] {
+        ClientEvent::Operation(ref op) => {
+            assert_eq!(op.apply(&"こんにちは".into()), "こんにちは世界")
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[2] {
+        ClientEvent::Buffering => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    // the remote operation is transformed against the buffered one
+    match events[3] {
+        ClientEvent::Operation(ref op) => assert_eq!(op.apply(&view), unsynced),
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[4] {
+        ClientEvent::Error(_) => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_charwise_checksum() {
+    let server = Rc::new(RefCell::new(Server::new()));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut client = block_on(Client::with_connection(&connection)).unwrap();
+    let events = client.subscribe();
+
+    other.push_operation({
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        assert_eq!(patch.checksum, Some(ot::hash_content("世界")));
+        assert_eq!(patch.checksum, server.borrow().current_state().checksum);
+        other.apply_response(patch).unwrap();
+    }
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+    {
+        // pretend that the client applied a broken diff
+        let mut patch = block_on(client.send_get_patch()).unwrap();
+        patch.checksum = patch.checksum.map(|checksum| checksum.wrapping_add(1));
+        match client.apply_patch(patch) {
+            Err(ClientError::Diverged) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    {
+        let state = block_on(client.reload()).unwrap();
+        client.reset(state).unwrap();
+    }
+    assert_eq!(client.current_content().unwrap(), "世界");
+    assert_eq!(client.unsynced_content().unwrap(), "世界");
+    assert!(client.send_to_server().is_err());
+    drop(client);
+
+    let events = block_on(events.collect::<Vec<_>>()).unwrap();
+    assert_eq!(events.len(), 3);
+    match events[0] {
+        ClientEvent::Error(_) => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[1] {
+        ClientEvent::Operation(_) => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[2] {
+        ClientEvent::Reset => {}
+        ref event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_charwise_reset_while_waiting() {
+    let server = Rc::new(RefCell::new(Server::new()));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut client = block_on(Client::with_connection(&connection)).unwrap();
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    });
+    let _response = block_on(client.send_to_server().unwrap()).unwrap();
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into()).retain("世界".len());
+        op
+    });
+
+    // the client can be reset before the response arrives
+    {
+        let state = block_on(client.reload()).unwrap();
+        client.reset(state).unwrap();
+    }
+    assert_eq!(client.current_content().unwrap(), "世界");
+    assert_eq!(client.unsynced_content().unwrap(), "世界");
+    assert!(client.send_to_server().is_err());
+}
+
+#[test]
+fn test_charwise_driver() {
+    use ot::cs::driver::Driver;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let client = block_on(Client::with_connection(mock_connection::MockConnection::new(
+        server.clone(),
+    ))).unwrap();
+
+    other.push_operation({
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+
+    let (driver, handle) = Driver::new(client, Duration::from_millis(20), Duration::from_secs(60));
+    let events = handle.subscribe();
+
+    // the driver polls for the patch at first
+    let editor = events
+        .next()
+        .map_err(|(never, _)| never.never_into())
+        .map(move |(event, _)| {
+            match event {
+                Some(ClientEvent::Operation(_)) => {}
+                event => panic!("unexpected event {:?}", event),
+            }
+            assert_eq!(handle.unsynced_content().unwrap(), "世界");
+
+            // these are sent together after the debounce duration
+            handle.push_operation({
+                let mut op = Operation::new();
+                op.insert("こんにちは".into()).retain("世界".len());
+                op
+            });
+            handle.push_operation({
+                let mut op = Operation::new();
+                op.retain("こんにちは".len())
+                    .insert(" ".into())
+                    .retain("世界".len());
+                op
+            });
+        });
+
+    let (client, ()) = block_on(driver.join(editor)).unwrap();
+
+    assert_eq!(client.current_content().unwrap(), "こんにちは 世界");
+    assert_eq!(server.borrow().current_state().id, Id(2));
+    assert_eq!(server.borrow().current_state().content, "こんにちは 世界");
+}
+
+#[test]
+fn test_charwise_driver_rejection() {
+    use ot::cs::driver::Driver;
+    use ot::Operation as OperationTrait;
+    use futures::{Async, Stream};
+    use futures::future::poll_fn;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .add_validator(|state: &State<Operation>, op: &Operation, _: &Metadata| {
+            if op.apply(&state.content).contains('#') {
+                Err(Rejection::new("forbidden", "# is not allowed"))
+            } else {
+                Ok(())
+            }
+        });
+
+    let client = block_on(Client::with_connection(mock_connection::MockConnection::new(
+        server.clone(),
+    ))).unwrap();
+    let (driver, handle) = Driver::new(client, Duration::from_millis(20), Duration::from_secs(60));
+    let mut events = handle.subscribe();
+    handle.push_operation({
+        let mut op = Operation::new();
+        op.insert("#".into());
+        op
+    });
+
+    // the driver keeps running after the rejection
+    let mut handle = Some(handle);
+    let editor = poll_fn(move |cx| loop {
+        match events.poll_next(cx) {
+            Ok(Async::Ready(Some(ClientEvent::Rejected(rejection)))) => {
+                assert_eq!(rejection.code, "forbidden");
+                let handle = handle.take().unwrap();
+                assert_eq!(handle.unsynced_content().unwrap(), "");
+                handle.push_operation({
+                    let mut op = Operation::new();
+                    op.insert("hello".into());
+                    op
+                });
+                return Ok(Async::Ready(()));
+            }
+            Ok(Async::Ready(Some(_))) => {}
+            Ok(Async::Ready(None)) => panic!("the driver finished before the rejection"),
+            Ok(Async::Pending) => return Ok(Async::Pending),
+            Err(never) => return Err(never.never_into()),
+        }
+    });
+
+    let (client, ()) = block_on(driver.join(editor)).unwrap();
+
+    assert_eq!(client.current_content().unwrap(), "hello");
+    assert_eq!(server.borrow().current_state().id, Id(1));
+    assert_eq!(server.borrow().current_state().content, "hello");
+
+    // a client in the error state can not be driven
+    let client: Client<Operation, mock_connection::MockConnection<Operation>> =
+        Client::Error("broken".into());
+    let (driver, handle) = Driver::new(client, Duration::from_millis(20), Duration::from_secs(60));
+    match block_on(driver) {
+        Err(ClientError::NotConnected(_)) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    drop(handle);
+}
+
+#[test]
+fn test_charwise_pipelined_client() {
+    use ot::cs::pipelined_client::PipelinedClient;
+
+    let server = Rc::new(RefCell::new(Server::new()));
+
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut other = block_on(Client::with_connection(&connection)).unwrap();
+    let mut client = block_on(PipelinedClient::with_connection(
+        mock_connection::MockConnection::new(server.clone()),
+        2,
+    )).unwrap();
+    let mut responses = VecDeque::new();
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+    responses.push_back(client.send_to_server().unwrap());
+
+    other.push_operation({
+        let mut op = Operation::new();
+        op.insert("!".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+
+    // built on top of the operation in flight
+    client.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは".len()).insert(" 世界".into());
+        op
+    });
+    responses.push_back(client.send_to_server().unwrap());
+
+    client.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは 世界".len()).insert("?".into());
+        op
+    });
+    assert!(client.send_to_server().is_err());
+    assert!(block_on(client.send_get_patch()).is_err());
+    assert_eq!(client.in_flight(), 2);
+    assert_eq!(client.unsynced_content(), "こんにちは 世界?");
+
+    while let Some(response) = responses.pop_front() {
+        let patch = block_on(response).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+    assert_eq!(client.current_content(), "!こんにちは 世界");
+    assert_eq!(client.unsynced_content(), "!こんにちは 世界?");
+
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+    {
+        let patch = block_on(other.send_get_patch()).unwrap();
+        other.apply_patch(patch).unwrap();
+    }
+
+    assert_eq!(server.borrow().current_state().content, "!こんにちは 世界?");
+    assert_eq!(client.current_content(), "!こんにちは 世界?");
+    assert_eq!(other.current_content().unwrap(), "!こんにちは 世界?");
+
+    // checksums are verified in the same way as Client
+    other.push_operation({
+        let mut op = Operation::new();
+        op.retain("!こんにちは 世界?".len()).insert("!".into());
+        op
+    });
+    {
+        let patch = block_on(other.send_to_server().unwrap()).unwrap();
+        other.apply_response(patch).unwrap();
+    }
+    {
+        let mut patch = block_on(client.send_get_patch()).unwrap();
+        patch.checksum = patch.checksum.map(|checksum| checksum.wrapping_add(1));
+        match client.apply_patch(patch) {
+            Err(ClientError::Diverged) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        let state = block_on(client.reload()).unwrap();
+        client.reset(state).unwrap();
+    }
+    assert_eq!(client.current_content(), "!こんにちは 世界?!");
+}
+
+#[test]
+fn fuzz_test_charwise_pipelined_client() {
+    use ot::cs::pipelined_client::PipelinedClient;
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..20 {
+        let server = Rc::new(RefCell::new(Server::new()));
+        // random operations on an empty string do nothing
+        server
+            .borrow_mut()
+            .modify(Id(0), {
+                let mut op = Operation::new();
+                op.insert(random_string(&mut rng, 20));
+                op
+            })
+            .unwrap();
+
+        let connection = mock_connection::MockConnection::new(server.clone());
+        let mut other = block_on(Client::with_connection(&connection)).unwrap();
+        let mut client = block_on(PipelinedClient::with_connection(
+            mock_connection::MockConnection::new(server.clone()),
+            4,
+        )).unwrap();
+        let mut responses = VecDeque::new();
+
+        for _ in 0..50 {
+            match rng.gen_range(0, 4) {
+                0 => {
+                    let content = client.unsynced_content();
+                    client.push_operation(random_operation(&mut rng, &content));
+                    if let Ok(response) = client.send_to_server() {
+                        responses.push_back(response);
+                    }
+                }
+                1 => if let Some(response) = responses.pop_front() {
+                    let patch = block_on(response).unwrap();
+                    client.apply_response(patch).unwrap();
+                },
+                // the responses of the previous operations may be lost
+                2 => if rng.gen() {
+                    for response in client.resend() {
+                        block_on(response).unwrap();
+                    }
+                },
+                _ => {
+                    let patch = block_on(other.send_get_patch()).unwrap();
+                    other.apply_patch(patch).unwrap();
+                    let content = other.unsynced_content().unwrap();
+                    other.push_operation(random_operation(&mut rng, &content));
+                    let patch = block_on(other.send_to_server().unwrap()).unwrap();
+                    other.apply_response(patch).unwrap();
+                }
+            }
+        }
+
+        while let Some(response) = responses.pop_front() {
+            let patch = block_on(response).unwrap();
+            client.apply_response(patch).unwrap();
+        }
+        if let Ok(response) = client.send_to_server() {
+            let patch = block_on(response).unwrap();
+            client.apply_response(patch).unwrap();
+        }
+        {
+            let patch = block_on(client.send_get_patch()).unwrap();
+            client.apply_patch(patch).unwrap();
+            let patch = block_on(other.send_get_patch()).unwrap();
+            other.apply_patch(patch).unwrap();
+        }
+
+        let content = server.borrow().current_state().content.clone();
+        assert_eq!(client.current_content(), content);
+        assert_eq!(client.unsynced_content(), content);
+        assert_eq!(other.current_content().unwrap(), content);
+    }
+}
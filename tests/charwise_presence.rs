@@ -0,0 +1,233 @@
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::presence::*;
+use ot::server::*;
+use ot::client::*;
+use ot::client::Connection;
+use ot::selection::charwise::Selection;
+use ot::selection::Bias;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+extern crate futures;
+use futures::executor::block_on;
+use futures::StreamExt;
+
+#[test]
+fn test_charwise_presence() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .modify(Id(0), {
+            let mut op = Operation::new();
+            op.insert("hello world".into());
+            op
+        })
+        .unwrap();
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    let mut hub = PresenceHub::new();
+    let events = hub.subscribe();
+
+    let presence = client1
+        .presence(vec![Selection::Cursor(5, Bias::Right)])
+        .unwrap()
+        .unwrap();
+    hub.update(&server.borrow(), presence).unwrap();
+    // presence does not make a new state
+    assert_eq!(server.borrow().current_state().id, Id(1));
+    assert_eq!(
+        client2.remote_presence(hub.get(&connection1.client_id()).unwrap().clone()),
+        Some(vec![Selection::Cursor(5, Bias::Right)])
+    );
+
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.insert("oh, ".into()).retain("hello world".len());
+        op
+    });
+    // presence on unsynced operations is moved back to the state the client is based on
+    let presence = client2
+        .presence(vec![Selection::Cursor(2, Bias::Right), Selection::Cursor(9, Bias::Right)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(presence.id, Id(1));
+    assert_eq!(
+        presence.data,
+        vec![Selection::Cursor(0, Bias::Right), Selection::Cursor(5, Bias::Right)]
+    );
+    {
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_response(patch).unwrap();
+    }
+    hub.catch_up(&server.borrow()).unwrap();
+
+    let presence = hub.get(&connection1.client_id()).unwrap().clone();
+    assert_eq!(presence.id, Id(2));
+    assert_eq!(presence.data, vec![Selection::Cursor(9, Bias::Right)]);
+
+    // the presence is not on the state client1 is based on yet
+    assert_eq!(client1.remote_presence(presence.clone()), None);
+    {
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+    // it is moved along with the unsynced operations
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.insert("Oh! ".into()).retain("oh, hello world".len());
+        op
+    });
+    assert_eq!(
+        client1.remote_presence(presence),
+        Some(vec![Selection::Cursor(13, Bias::Right)])
+    );
+
+    // a client behind the server still moves its presence, which is transformed to the
+    // current state
+    hub.update(
+        &server.borrow(),
+        Presence {
+            client_id: connection1.client_id(),
+            id: Id(1),
+            data: vec![Selection::Cursor("hello world".len(), Bias::Right)],
+        },
+    ).unwrap();
+    assert_eq!(
+        hub.get(&connection1.client_id()).unwrap().data,
+        vec![Selection::Cursor("oh, hello world".len(), Bias::Right)]
+    );
+
+    // once it reported a newer state, an older presence does not overwrite it
+    hub.update(
+        &server.borrow(),
+        Presence {
+            client_id: connection1.client_id(),
+            id: Id(2),
+            data: vec![Selection::Cursor(9, Bias::Right)],
+        },
+    ).unwrap();
+    hub.update(
+        &server.borrow(),
+        Presence {
+            client_id: connection1.client_id(),
+            id: Id(1),
+            data: vec![Selection::Cursor(0, Bias::Right)],
+        },
+    ).unwrap();
+    assert_eq!(
+        hub.get(&connection1.client_id()).unwrap().data,
+        vec![Selection::Cursor(9, Bias::Right)]
+    );
+
+    let presence = client2
+        .presence(vec![Selection::Range(0, 3, Bias::Right, Bias::Right)])
+        .unwrap()
+        .unwrap();
+    hub.update(&server.borrow(), presence).unwrap();
+    hub.remove(&connection1.client_id());
+    assert_eq!(server.borrow().current_state().id, Id(2));
+
+    drop(hub);
+    let events = block_on(events.collect::<Vec<_>>()).unwrap();
+    assert_eq!(events.len(), 6);
+    match events[0] {
+        PresenceEvent::Update(ref presence) => {
+            assert_eq!(presence.id, Id(1));
+            assert_eq!(presence.data, vec![Selection::Cursor(5, Bias::Right)]);
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[1] {
+        PresenceEvent::Update(ref presence) => {
+            assert_eq!(presence.id, Id(2));
+            assert_eq!(presence.data, vec![Selection::Cursor(9, Bias::Right)]);
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[2] {
+        PresenceEvent::Update(ref presence) => {
+            assert_eq!(presence.id, Id(2));
+            assert_eq!(
+                presence.data,
+                vec![Selection::Cursor("oh, hello world".len(), Bias::Right)]
+            );
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[3] {
+        PresenceEvent::Update(ref presence) => {
+            assert_eq!(presence.id, Id(2));
+            assert_eq!(presence.data, vec![Selection::Cursor(9, Bias::Right)]);
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[4] {
+        PresenceEvent::Update(ref presence) => {
+            assert_eq!(presence.client_id, connection2.client_id());
+            assert_eq!(presence.data, vec![Selection::Range(0, 3, Bias::Right, Bias::Right)]);
+        }
+        ref event => panic!("unexpected event {:?}", event),
+    }
+    match events[5] {
+        PresenceEvent::Leave(ref client_id) => assert_eq!(*client_id, connection1.client_id()),
+        ref event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_charwise_shared_presence() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .modify(Id(0), {
+            let mut op = Operation::new();
+            op.insert("hello world".into());
+            op
+        })
+        .unwrap();
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    let hub = SharedPresenceHub::new(PresenceHub::new());
+    server.borrow_mut().add_observer(hub.clone());
+
+    let presence = client1
+        .presence(vec![Selection::Cursor(5, Bias::Right)])
+        .unwrap()
+        .unwrap();
+    hub.lock().unwrap().update(&server.borrow(), presence).unwrap();
+
+    // the hub follows the operations accepted by the server
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.insert("oh, ".into()).retain("hello world".len());
+        op
+    });
+    {
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_response(patch).unwrap();
+    }
+    server
+        .borrow_mut()
+        .modify(Id(2), {
+            let mut op = Operation::new();
+            op.retain("oh, hello world".len()).insert("!".into());
+            op
+        })
+        .unwrap();
+
+    let presence = hub.lock().unwrap().get(&connection1.client_id()).unwrap().clone();
+    assert_eq!(presence.id, Id(3));
+    assert_eq!(presence.data, vec![Selection::Cursor(9, Bias::Right)]);
+}
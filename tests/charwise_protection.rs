@@ -0,0 +1,304 @@
+extern crate ot;
+
+use ot::protection::*;
+use ot::charwise::Operation as BaseOperation;
+use ot::Operation as OperationTrait;
+use ot::cs::*;
+use ot::server::*;
+use ot::client::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+extern crate serde_json;
+
+extern crate futures;
+use futures::executor::block_on;
+
+const HEADER: &str = "// generated\n";
+
+fn document() -> Operation<BaseOperation> {
+    let mut op = Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert(HEADER.into()).insert("hello world".into());
+        op
+    });
+    op.protect(0, HEADER.len());
+    op
+}
+
+#[test]
+fn test_check() {
+    let target = document().apply(&Target::default());
+    assert_eq!(target.ranges, vec![Range::new(0, HEADER.len())]);
+
+    // inserting at either end is fine
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.insert("#!".into()).retain(HEADER.len() + "hello world".len());
+                op
+            })
+            .is_ok()
+    );
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(HEADER.len())
+                    .insert("hello".into())
+                    .retain("hello world".len());
+                op
+            })
+            .is_ok()
+    );
+
+    // editing inside is not
+    let rejection = target
+        .check(&{
+            let mut op = BaseOperation::new();
+            op.retain("// gen".len())
+                .insert("!".into())
+                .retain("erated\nhello world".len());
+            op
+        })
+        .unwrap_err();
+    assert_eq!(rejection.code, "protected");
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain("// generated".len())
+                    .delete("\n".len())
+                    .insert(" ".into())
+                    .retain("hello world".len());
+                op
+            })
+            .is_err()
+    );
+
+    // the range follows edits
+    let target = target
+        .operate({
+            let mut op = BaseOperation::new();
+            op.insert("#!".into()).retain(HEADER.len() + "hello world".len());
+            op
+        })
+        .apply(&target);
+    assert_eq!(
+        target.ranges,
+        vec![Range::new("#!".len(), "#!".len() + HEADER.len())]
+    );
+
+    // and is removed by an operation changing the ranges
+    let mut op = target.operate(BaseOperation::nop(&target.base));
+    op.unprotect("#!".len(), "#!".len() + HEADER.len());
+    assert!(op.apply(&target).ranges.is_empty());
+}
+
+#[test]
+fn test_transform() {
+    let target = document().apply(&Target::default());
+
+    // the server protects another range while a client edits
+    let mut left = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(HEADER.len() + "hello world".len())
+            .insert("\n// generated".into());
+        op
+    });
+    left.protect(
+        HEADER.len() + "hello world".len(),
+        HEADER.len() + "hello world\n// generated".len(),
+    );
+    let right = target.operate({
+        let mut op = BaseOperation::new();
+        op.insert("#!\n".into())
+            .retain(HEADER.len() + "hello world".len());
+        op
+    });
+
+    let (left_, right_) = left.clone().transform(right.clone());
+    let left = left.compose(right_).apply(&target);
+    let right = right.compose(left_).apply(&target);
+    assert_eq!(left, right);
+    assert_eq!(
+        left.ranges,
+        vec![
+            Range::new("#!\n".len(), "#!\n".len() + HEADER.len()),
+            Range::new(
+                "#!\n".len() + HEADER.len() + "hello world".len(),
+                left.base.len(),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_charwise_protection_client_server() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server.borrow_mut().modify(Id(0), document()).unwrap();
+    server.borrow_mut().add_validator(Guard);
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    // clients receive the ranges with the content
+    assert_eq!(
+        client1.current_content().unwrap().ranges,
+        vec![Range::new(0, HEADER.len())]
+    );
+
+    // the protected range follows accepted operations
+    client2.push_operation(Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("#!\n".into())
+            .retain(HEADER.len() + "hello world".len());
+        op
+    }));
+    {
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_response(patch).unwrap();
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+    let edit = {
+        let mut op = BaseOperation::new();
+        op.retain("#!\n// ".len())
+            .delete("generated".len())
+            .insert("edited".into())
+            .retain("\nhello world".len());
+        op
+    };
+    assert!(client1.unsynced_content().unwrap().check(&edit).is_err());
+
+    // the server rejects the operation even if the client sends it
+    client1.push_operation(Operation::with_content(edit));
+    let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+    match client1.apply_response(patch) {
+        Err(ClientError::Rejected(rejection)) => assert_eq!(rejection.code, "protected"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(
+        client1.unsynced_content().unwrap().base,
+        "#!\n// generated\nhello world"
+    );
+    assert_eq!(
+        server.borrow().current_state().content.base,
+        "#!\n// generated\nhello world"
+    );
+
+    // and so does it when the client tries to remove the range
+    client1.push_operation({
+        let content = client1.current_content().unwrap();
+        let mut op = content.operate(BaseOperation::nop(&content.base));
+        op.unprotect("#!\n".len(), "#!\n".len() + HEADER.len());
+        op
+    });
+    let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+    assert!(client1.apply_response(patch).is_err());
+    assert_eq!(server.borrow().current_state().content.ranges.len(), 1);
+
+    // editing outside is accepted
+    client1.push_operation(Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.retain("#!\n// generated\n".len())
+            .delete("hello".len())
+            .insert("goodbye".into())
+            .retain(" world".len());
+        op
+    }));
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+    }
+    assert_eq!(
+        server.borrow().current_state().content.base,
+        "#!\n// generated\ngoodbye world"
+    );
+
+    // the ranges are kept when the server is persisted or forked
+    let json = serde_json::to_string(&*server.borrow()).unwrap();
+    let persisted: Server<Operation<BaseOperation>> = serde_json::from_str(&json).unwrap();
+    let fork = persisted.fork(&Id(3)).unwrap();
+    assert_eq!(
+        fork.current_state().content.ranges,
+        vec![Range::new("#!\n".len(), "#!\n".len() + HEADER.len())]
+    );
+}
+
+#[test]
+fn test_charwise_protection_pending() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server.borrow_mut().modify(Id(0), document()).unwrap();
+    server.borrow_mut().add_validator(Guard);
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    // a remote operation is accepted by the server
+    client2.push_operation(Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("// b\n".into())
+            .retain(HEADER.len() + "hello world".len());
+        op
+    }));
+    {
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_response(patch).unwrap();
+    }
+
+    // while client1 has an operation in flight and another in the buffer
+    client1.push_operation(Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("#!\n".into())
+            .retain(HEADER.len() + "hello world".len());
+        op
+    }));
+    let response = client1.send_to_server().unwrap();
+    client1.push_operation(Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("// a\n".into())
+            .retain("#!\n".len() + HEADER.len() + "hello world".len());
+        op
+    }));
+    client1.apply_response(block_on(response).unwrap()).unwrap();
+
+    // the ranges of the unsynced content are on top of the local operations
+    let content = client1.unsynced_content().unwrap();
+    let start = content.base.find(HEADER).unwrap();
+    assert_eq!(start, "// a\n#!\n// b\n".len());
+    assert_eq!(content.ranges, vec![Range::new(start, start + HEADER.len())]);
+    let inside = {
+        let mut op = BaseOperation::new();
+        op.retain(start + "// ".len())
+            .insert("!".into())
+            .retain("generated\nhello world".len());
+        op
+    };
+    let outside = {
+        let mut op = BaseOperation::new();
+        op.retain(start - "\n".len())
+            .insert("!".into())
+            .retain("\n// generated\nhello world".len());
+        op
+    };
+    assert!(content.check(&inside).is_err());
+    assert!(content.check(&outside).is_ok());
+
+    // and the server agrees
+    client1.push_operation(Operation::with_content(outside));
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+    }
+    assert_eq!(
+        server.borrow().current_state().content,
+        client1.current_content().unwrap()
+    );
+}
@@ -1,24 +1,36 @@
 extern crate ot;
 use ot::selection::charwise::*;
+use ot::selection::Bias;
 use ot::charwise::Operation as BaseOperation;
 use ot::Operation as OperationTrait;
 
 mod util;
+use util::charwise_selection::*;
 
 extern crate rand;
 
+use std::collections::HashMap;
+
 #[test]
 fn test_apply() {
     use ot::selection::charwise::Selection::*;
 
     let target = Target {
         base: "こんにちは 世界".into(),
-        selection: vec![
-            Range("こんにちは".len(), "こんにちは ".len()),
-            Cursor("こんにちは 世界".len()),
-        ],
+        selection: {
+            let mut selection = HashMap::new();
+            selection.insert(
+                0,
+                vec![
+                    Range("こんにちは".len(), "こんにちは ".len(), Bias::Right, Bias::Right),
+                    Cursor("こんにちは 世界".len(), Bias::Right),
+                ],
+            );
+            selection.insert(1, vec![Cursor(0, Bias::Right)]);
+            selection
+        },
     };
-    let op = Operation::Operate({
+    let op = target.operate({
         let mut op = BaseOperation::new();
         op.retain("こんにちは".len())
             .insert("!".into())
@@ -32,12 +44,20 @@ fn test_apply() {
         op.apply(&target),
         Target {
             base: "こんにちは! 社会".into(),
-            selection: vec![
-                // this behavior (extension of range) might be a specification bug
-                // but hackmd seems to have the same behavior, so it's ok for now
-                Range("こんにちは!".len(), "こんにちは! 社会".len()),
-                Cursor("こんにちは! 社会".len()),
-            ],
+            selection: {
+                let mut selection = HashMap::new();
+                selection.insert(
+                    0,
+                    vec![
+                        // this behavior (extension of range) might be a specification bug
+                        // but hackmd seems to have the same behavior, so it's ok for now
+                        Range("こんにちは!".len(), "こんにちは! 社会".len(), Bias::Right, Bias::Right),
+                        Cursor("こんにちは! 社会".len(), Bias::Right),
+                    ],
+                );
+                selection.insert(1, vec![Cursor(0, Bias::Right)]);
+                selection
+            },
         }
     );
 }
@@ -48,24 +68,22 @@ fn test_compose() {
 
     let target = Target {
         base: "こんにちは 世界".into(),
-        selection: vec![],
+        selection: to_selection(vec![
+            Range("こんにちは".len(), "こんにちは ".len(), Bias::Right, Bias::Right),
+            Cursor("こんにちは 世界".len(), Bias::Right),
+        ]),
     };
-    let first = Operation::Both(
-        vec![
-            Range("こんにちは!".len(), "こんにちは! ".len()),
-            Cursor("こんにちは! 世界".len()),
-        ],
-        {
-            let mut op = BaseOperation::new();
-            op.retain("こんにちは".len())
-                .insert("!".into())
-                .retain(" ".len())
-                .delete("世界".len())
-                .insert("社会".into());
-            op
-        },
-    );
-    let second = Operation::Operate({
+    let first = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("こんにちは".len())
+            .insert("!".into())
+            .retain(" ".len())
+            .delete("世界".len())
+            .insert("社会".into());
+        op
+    });
+    let applied = first.apply(&target);
+    let second = applied.operate({
         let mut op = BaseOperation::new();
         op.delete("こんにちは".len())
             .insert("さようなら".into())
@@ -77,24 +95,14 @@ fn test_compose() {
         second.apply(&first.apply(&target)),
         first.clone().compose(second.clone()).apply(&target)
     );
-    assert_eq!(
-        second.apply(&first.apply(&target)),
-        Target {
-            base: "さようなら! 社会".into(),
-            selection: vec![
-                Range("さようなら!".len(), "さようなら! ".len()),
-                Cursor("さようなら! 社会".len()),
-            ],
-        },
-    );
     assert_eq!(
         first.compose(second).apply(&target),
         Target {
             base: "さようなら! 社会".into(),
-            selection: vec![
-                Range("さようなら!".len(), "さようなら! ".len()),
-                Cursor("さようなら! 社会".len()),
-            ],
+            selection: to_selection(vec![
+                Range("さようなら!".len(), "さようなら! 社会".len(), Bias::Right, Bias::Right),
+                Cursor("さようなら! 社会".len(), Bias::Right),
+            ]),
         },
     );
 }
@@ -105,30 +113,35 @@ fn test_transform() {
 
     let target = Target {
         base: "こんにちは 世界".into(),
-        selection: vec![],
+        selection: to_selection(vec![
+            Range("こんにちは".len(), "こんにちは ".len(), Bias::Right, Bias::Right),
+            Cursor("こんにちは 世界".len(), Bias::Right),
+        ]),
     };
-    let left = Operation::Both(
-        vec![
-            Range("こんにちは!".len(), "こんにちは! ".len()),
-            Cursor("こんにちは! 世界".len()),
-        ],
+    let left = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain("こんにちは".len())
+            .insert("!".into())
+            .retain(" ".len())
+            .delete("世界".len())
+            .insert("社会".into());
+        op
+    });
+    // another user moves their cursor along with the edit
+    let right = Operation::Op(
+        {
+            let mut selection = HashMap::new();
+            selection.insert(1, vec![Cursor("さ".len(), Bias::Right)]);
+            selection
+        },
         {
             let mut op = BaseOperation::new();
-            op.retain("こんにちは".len())
-                .insert("!".into())
-                .retain(" ".len())
-                .delete("世界".len())
-                .insert("社会".into());
+            op.delete("こんにちは".len())
+                .insert("さようなら".into())
+                .retain(" 世界".len());
             op
         },
     );
-    let right = Operation::Both(vec![Cursor("こ".len())], {
-        let mut op = BaseOperation::new();
-        op.delete("こんにちは".len())
-            .insert("さようなら".into())
-            .retain(" 世界".len());
-        op
-    });
 
     let (left_, right_) = left.clone().transform(right.clone());
     let composed_left = left.compose(right_);
@@ -139,62 +152,105 @@ fn test_transform() {
         composed_left.apply(&target),
         Target {
             base: "!さようなら 社会".into(),
-            selection: vec![
-                Range("!さようなら".len(), "!さようなら ".len()),
-                Cursor("!さようなら 社会".len()),
-            ],
+            selection: {
+                let mut selection = HashMap::new();
+                selection.insert(
+                    0,
+                    vec![
+                        Range("!さようなら".len(), "!さようなら 社会".len(), Bias::Right, Bias::Right),
+                        Cursor("!さようなら 社会".len(), Bias::Right),
+                    ],
+                );
+                selection.insert(1, vec![Cursor("!さ".len(), Bias::Right)]);
+                selection
+            },
         }
     );
+
+    // the selections of the same user are taken from the left
+    let (left_, right_) = target
+        .select(to_selection(vec![Cursor(0, Bias::Right)]))
+        .transform(target.select(to_selection(vec![Cursor(1, Bias::Right)])));
+    assert_eq!(left_.apply(&target).selection, to_selection(vec![Cursor(0, Bias::Right)]));
+    assert_eq!(right_.apply(&target).selection, to_selection(vec![Cursor(0, Bias::Right)]));
+}
+
+#[test]
+fn test_bias() {
+    use ot::selection::charwise::Selection::*;
+    use ot::selection::Bias::*;
+
+    let target = Target {
+        base: "abc".into(),
+        selection: to_selection(vec![
+            Cursor(1, Left),
+            Cursor(1, Right),
+            // grows when typing at either end
+            Range(1, 2, Left, Right),
+            // never grows
+            Range(1, 2, Right, Left),
+        ]),
+    };
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(1)
+            .insert("XY".into())
+            .retain(1)
+            .insert("Z".into())
+            .retain(1);
+        op
+    });
+
     assert_eq!(
-        composed_left.apply(&target),
+        op.apply(&target),
         Target {
-            base: "!さようなら 社会".into(),
-            selection: vec![
-                Range("!さようなら".len(), "!さようなら ".len()),
-                Cursor("!さようなら 社会".len()),
-            ],
+            base: "aXYbZc".into(),
+            selection: to_selection(vec![
+                Cursor(1, Left),
+                Cursor(3, Right),
+                Range(1, 5, Left, Right),
+                Range(3, 4, Right, Left),
+            ]),
         }
     );
 }
 
-use rand::distributions::{Range, Sample};
-fn random_selection<R: rand::Rng>(rng: &mut R, num_selection: usize, len: usize) -> Vec<Selection> {
-    let mut range = Range::new(0, len + 1);
-    (0..rng.gen_range(0, num_selection))
-        .map(|_| {
-            let start = range.sample(rng);
-            let end = range.sample(rng);
-            if start == end {
-                Selection::Cursor(start)
-            } else {
-                Selection::Range(start.min(end), start.max(end))
-            }
-        })
-        .collect()
-}
+#[test]
+fn test_direction() {
+    use ot::selection::charwise::Selection::*;
+    use ot::selection::Bias::*;
 
-fn random_target<R: rand::Rng>(rng: &mut R, num_selection: usize, len: usize) -> Target {
-    let base = util::charwise::random_string(rng, len);
-    let selection = random_selection(rng, num_selection, len);
+    let target = Target {
+        base: "こんにちは 世界".into(),
+        selection: to_selection(vec![
+            // selected backward from the end of "こんにちは"
+            Range("こんにちは".len(), "こん".len(), Right, Right),
+            Range("こんにちは ".len(), "こんにちは 世界".len(), Left, Right),
+        ]),
+    };
+    assert!(target.selection[&0][0].is_backward());
+    assert_eq!(target.selection[&0][0].start(), "こん".len());
 
-    Target { base, selection }
-}
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.insert("!".into())
+            .retain("こんにちは ".len())
+            .delete("世界".len());
+        op
+    });
 
-fn random_operation<R: rand::Rng>(rng: &mut R, num_selection: usize, target: &Target) -> Operation {
-    use Operation::*;
-
-    let len = target.base.len();
-    match rng.gen_range(0, 4) {
-        0 => Nop,
-        1 => Select(random_selection(rng, num_selection, len)),
-        2 => Operate(util::charwise::random_operation(rng, &target.base)),
-        3 => {
-            let op = util::charwise::random_operation(rng, &target.base);
-            let selection = random_selection(rng, num_selection, op.target_len());
-            Both(selection, op)
+    assert_eq!(
+        op.apply(&target),
+        Target {
+            base: "!こんにちは ".into(),
+            selection: to_selection(vec![
+                // the direction is kept
+                Range("!こんにちは".len(), "!こん".len(), Right, Right),
+                // the selected text was deleted, and the head remains as a cursor
+                Cursor("!こんにちは ".len(), Right),
+            ]),
         }
-        _ => unreachable!(),
-    }
+    );
 }
 
 #[test]
@@ -203,10 +259,11 @@ fn test_random_operation() {
 
     let mut rng = rand::thread_rng();
     let len = rng.gen_range(32, 100);
+    let user_num = rng.gen_range(1, 5);
     let selection_num = rng.gen_range(1, 30);
-    let target = random_target(&mut rng, selection_num, len);
+    let target = random_target(&mut rng, user_num, selection_num, len);
 
-    let operation = random_operation(&mut rng, selection_num, &target);
+    let operation = random_operation(&mut rng, user_num, selection_num, &target);
 
     operation.apply(&target);
 }
@@ -219,13 +276,14 @@ fn fuzz_test_compose() {
 
     for _ in 0..100 {
         let len = rng.gen_range(32, 100);
+        let user_num = rng.gen_range(1, 5);
         let selection_num = rng.gen_range(1, 30);
-        let target = random_target(&mut rng, selection_num, len);
+        let target = random_target(&mut rng, user_num, selection_num, len);
 
-        let first = random_operation(&mut rng, selection_num, &target);
+        let first = random_operation(&mut rng, user_num, selection_num, &target);
         let applied = first.apply(&target);
 
-        let second = random_operation(&mut rng, selection_num, &applied);
+        let second = random_operation(&mut rng, user_num, selection_num, &applied);
 
         let double_applied = second.apply(&applied);
         let compose_applied = first.compose(second).apply(&target);
@@ -242,11 +300,12 @@ fn fuzz_test_transform() {
 
     for _ in 0..1000 {
         let len = rng.gen_range(32, 100);
+        let user_num = rng.gen_range(1, 5);
         let selection_num = rng.gen_range(1, 30);
-        let target = random_target(&mut rng, selection_num, len);
+        let target = random_target(&mut rng, user_num, selection_num, len);
 
-        let left = random_operation(&mut rng, selection_num, &target);
-        let right = random_operation(&mut rng, selection_num, &target);
+        let left = random_operation(&mut rng, user_num, selection_num, &target);
+        let right = random_operation(&mut rng, user_num, selection_num, &target);
 
         let (left_, right_) = left.clone().transform(right.clone());
 
@@ -0,0 +1,101 @@
+extern crate ot;
+
+use ot::selection::charwise::*;
+use ot::selection::Bias;
+use ot::cs::*;
+use ot::server::*;
+use ot::client::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+extern crate futures;
+use futures::executor::block_on;
+
+#[test]
+fn test_charwise_selection_client_server() {
+    use Selection::*;
+    use ot::charwise::Operation as BaseOperation;
+
+    let server: Rc<RefCell<Server<Operation<usize>>>> = Rc::new(RefCell::new(Server::new()));
+
+    let connection1 = mock_connection::MockConnection::new(server.clone());
+    let connection2 = mock_connection::MockConnection::new(server.clone());
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+
+    // both users type at the same time
+    client1.push_operation(Operation::Op(
+        {
+            let mut selection = HashMap::new();
+            selection.insert(1, vec![Cursor("こんにちは".len(), Bias::Right)]);
+            selection
+        },
+        {
+            let mut op = BaseOperation::new();
+            op.insert("こんにちは".into());
+            op
+        },
+    ));
+    client2.push_operation(Operation::Op(
+        {
+            let mut selection = HashMap::new();
+            selection.insert(2, vec![Cursor(0, Bias::Right)]);
+            selection
+        },
+        {
+            let mut op = BaseOperation::new();
+            op.insert("世界".into());
+            op
+        },
+    ));
+    {
+        let response1 = client1.send_to_server().unwrap();
+        let response2 = client2.send_to_server().unwrap();
+        client1.apply_response(block_on(response1).unwrap()).unwrap();
+        client2.apply_response(block_on(response2).unwrap()).unwrap();
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+
+    // everyone sees the cursors of both users
+    let expected = Target {
+        base: "世界こんにちは".into(),
+        selection: {
+            let mut selection = HashMap::new();
+            selection.insert(1, vec![Cursor("世界こんにちは".len(), Bias::Right)]);
+            selection.insert(2, vec![Cursor(0, Bias::Right)]);
+            selection
+        },
+    };
+    assert_eq!(client1.current_content().unwrap(), expected);
+    assert_eq!(client2.current_content().unwrap(), expected);
+    assert_eq!(server.borrow().current_state().content, expected);
+
+    // moving a cursor keeps the others
+    {
+        let content = client1.current_content().unwrap();
+        let mut selection = content.selection.clone();
+        selection.insert(1, vec![Range(0, "世界".len(), Bias::Right, Bias::Right)]);
+        client1.push_operation(content.select(selection));
+    }
+    {
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_response(patch).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+
+    let expected = Target {
+        base: "世界こんにちは".into(),
+        selection: {
+            let mut selection = HashMap::new();
+            selection.insert(1, vec![Range(0, "世界".len(), Bias::Right, Bias::Right)]);
+            selection.insert(2, vec![Cursor(0, Bias::Right)]);
+            selection
+        },
+    };
+    assert_eq!(client1.current_content().unwrap(), expected);
+    assert_eq!(client2.current_content().unwrap(), expected);
+}
@@ -0,0 +1,159 @@
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::shared_server::SharedServer;
+use ot::server::*;
+use ot::client::*;
+use ot::client::Connection;
+
+use std::thread;
+
+extern crate rand;
+use rand::Rng;
+
+extern crate futures;
+use futures::executor::block_on;
+
+fn assert_send<T: Send>(_: &T) {}
+
+// insert c at a random position of content
+fn random_insert<R: Rng>(rng: &mut R, content: &str, c: char) -> Operation {
+    let positions: Vec<_> = content
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(Some(content.len()))
+        .collect();
+    let position = *rng.choose(&positions).unwrap();
+
+    let mut op = Operation::new();
+    op.retain(position);
+    op.insert(c.to_string());
+    op.retain(content.len() - position);
+    op
+}
+
+#[test]
+fn test_charwise_shared_server() {
+    let server = SharedServer::<Operation>::new(Server::new());
+    let mut client = block_on(Client::with_connection(server.connect().unwrap())).unwrap();
+
+    let connection = server.connect().unwrap();
+    let future = connection.get_latest_state();
+    assert_send(&future);
+
+    // the connection can be moved to another thread
+    let handle = thread::spawn(move || {
+        let mut client = block_on(Client::with_connection(&connection)).unwrap();
+        client.push_operation({
+            let mut op = Operation::new();
+            op.insert("こんにちは".into());
+            op
+        });
+        let response = client.send_to_server().unwrap();
+        client.apply_response(block_on(response).unwrap()).unwrap();
+        client.current_content().unwrap()
+    });
+    assert_eq!(handle.join().unwrap(), "こんにちは");
+
+    server
+        .modify(Id(1), {
+            let mut op = Operation::new();
+            op.retain("こんにちは".len());
+            op.insert("世界".into());
+            op
+        })
+        .unwrap();
+
+    let patch = block_on(client.send_get_patch()).unwrap();
+    client.apply_patch(patch).unwrap();
+    assert_eq!(client.current_content().unwrap(), "こんにちは世界");
+    assert_eq!(server.current_state().unwrap().content, "こんにちは世界");
+}
+
+#[test]
+fn stress_test_charwise_shared_server() {
+    const THREADS: usize = 16;
+    const ROUNDS: usize = 50;
+
+    let server = SharedServer::<Operation>::new(Server::new());
+
+    // every thread inserts its own character
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let c = (b'a' + i as u8) as char;
+            let server = server.clone();
+            thread::spawn(move || {
+                let mut rng = rand::thread_rng();
+
+                if i % 2 == 0 {
+                    // modify the server directly, from a state which may be outdated
+                    for _ in 0..ROUNDS {
+                        let state = server.current_state().unwrap();
+                        let op = random_insert(&mut rng, &state.content, c);
+                        server.modify(state.id, op).unwrap();
+                    }
+                    None
+                } else {
+                    // edit through a client
+                    // the client owns its connection, so it can be sent back
+                    let connection = server.connect().unwrap();
+                    let mut client = block_on(Client::with_connection(connection)).unwrap();
+                    for _ in 0..ROUNDS {
+                        let op = random_insert(&mut rng, &client.current_content().unwrap(), c);
+                        client.push_operation(op);
+                        let response = client.send_to_server().unwrap();
+                        client.apply_response(block_on(response).unwrap()).unwrap();
+
+                        if rng.gen_weighted_bool(4) {
+                            let patch = block_on(client.send_get_patch()).unwrap();
+                            client.apply_patch(patch).unwrap();
+                        }
+                    }
+                    Some(client)
+                }
+            })
+        })
+        .collect();
+
+    let clients: Vec<_> = handles
+        .into_iter()
+        .filter_map(|handle| handle.join().unwrap())
+        .collect();
+
+    let content = server.current_state().unwrap().content;
+    assert_eq!(content.len(), THREADS * ROUNDS);
+    for i in 0..THREADS {
+        let c = (b'a' + i as u8) as char;
+        assert_eq!(content.chars().filter(|&d| d == c).count(), ROUNDS);
+    }
+
+    for mut client in clients {
+        let patch = block_on(client.send_get_patch()).unwrap();
+        client.apply_patch(patch).unwrap();
+        assert_eq!(client.current_content().unwrap(), content);
+    }
+}
+
+#[test]
+fn test_charwise_shared_server_poisoned() {
+    let server = SharedServer::<Operation>::new(Server::new());
+    let connection = server.connect().unwrap();
+
+    // a thread panics while holding the lock
+    {
+        let server = server.clone();
+        assert!(
+            thread::spawn(move || {
+                let _guard = server.lock().unwrap();
+                panic!("poison the lock");
+            }).join()
+                .is_err()
+        );
+    }
+
+    assert!(server.lock().is_err());
+    assert!(server.current_state().is_err());
+    assert!(server.connect().is_err());
+    assert!(block_on(connection.get_latest_state()).is_err());
+}
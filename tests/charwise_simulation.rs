@@ -0,0 +1,126 @@
+#![cfg(feature = "simulation")]
+
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::simulation::{check_convergence, Network, SimulationError};
+use ot::client::*;
+
+extern crate rand;
+
+extern crate futures;
+use futures::executor::block_on;
+
+mod util;
+use util::charwise::random_operation;
+
+fn insert(s: &str) -> Operation {
+    let mut op = Operation::new();
+    op.insert(s.into());
+    op
+}
+
+#[test]
+fn test_charwise_simulation() {
+    let network = Network::new(0);
+
+    let connection1 = network.connect();
+    let connection2 = network.connect();
+    // the initial states are pushed
+    assert_eq!(network.pending(), 2);
+    network.deliver(0);
+    assert_eq!(connection1.pushed_state().unwrap().id, Id(0));
+    assert!(connection2.pushed_state().is_none());
+
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+    assert_eq!(network.pending(), 0);
+
+    client1.push_operation(insert("こんにちは"));
+    client2.push_operation(insert("世界"));
+    let response1 = client1.send_to_server().unwrap();
+    let response2 = client2.send_to_server().unwrap();
+
+    // the requests arrive in the reverse order, and the first one twice
+    assert_eq!(network.pending(), 2);
+    network.duplicate(0);
+    network.deliver(1);
+    network.deliver(0);
+    network.deliver(0);
+    assert_eq!(
+        network.with_server(|server| server.current_state().content.clone()),
+        "こんにちは世界"
+    );
+
+    // the reply to client1 and the one to the copy are lost
+    assert_eq!(network.pending(), 3);
+    network.lose(1);
+    network.lose(1);
+    match block_on(response1) {
+        Err(SimulationError::Lost) => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(patch) => panic!("unexpected patch {:?}", patch.id),
+    }
+    client2.apply_response(block_on(response2).unwrap()).unwrap();
+
+    // the resent operation is not applied twice
+    {
+        let patch = block_on(client1.resync()).unwrap();
+        client1.apply_patch(patch).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+    assert_eq!(client1.current_content().unwrap(), "こんにちは世界");
+    assert_eq!(client2.current_content().unwrap(), "こんにちは世界");
+
+    // a partitioned client cannot reach the server until healed
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.retain("こんにちは世界".len()).insert("!".into());
+        op
+    });
+    network.partition(&connection2.client_id());
+    assert!(block_on(client2.send_to_server().unwrap()).is_err());
+    network.heal(&connection2.client_id());
+    {
+        let patch = block_on(client2.resync()).unwrap();
+        client2.apply_patch(patch).unwrap();
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+    }
+    assert_eq!(client1.current_content().unwrap(), "こんにちは世界!");
+    assert_eq!(client2.current_content().unwrap(), "こんにちは世界!");
+}
+
+#[test]
+fn test_charwise_simulation_lost_network() {
+    // no client can connect if every message is lost
+    let network = Network::<Operation>::new(0);
+    network.set_drop_rate(1.0);
+    let result = check_convergence(&network, 2, 10, |rng, content| random_operation(rng, content));
+    assert!(result.is_err());
+}
+
+#[test]
+fn fuzz_test_charwise_simulation() {
+    for seed in 0..20 {
+        let run = || {
+            let network = Network::<Operation>::new(seed);
+            network.set_drop_rate(0.1);
+            network.set_duplicate_rate(0.1);
+            // random operations on an empty string do nothing
+            network
+                .with_server(|server| server.modify(Id(0), insert("こんにちは, world!")))
+                .unwrap();
+            check_convergence(&network, 4, 200, |rng, content| {
+                random_operation(rng, content)
+            })
+        };
+
+        let content = run().unwrap();
+        assert!(content != "こんにちは, world!");
+        // the same seed gives the same session
+        assert_eq!(run().unwrap(), content);
+    }
+}
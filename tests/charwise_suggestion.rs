@@ -0,0 +1,107 @@
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::suggestion::*;
+use ot::server::*;
+use ot::client::*;
+use ot::Operation as OperationTrait;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+extern crate futures;
+use futures::executor::block_on;
+
+#[test]
+fn test_charwise_suggestion() {
+    let server = Rc::new(RefCell::new(Server::new()));
+    server
+        .borrow_mut()
+        .modify(Id(0), {
+            let mut op = Operation::new();
+            op.insert("hello world".into());
+            op
+        })
+        .unwrap();
+
+    let mut suggestions = Suggestions::new();
+
+    // a reviewer suggests a change, which does not touch the document
+    let fix = suggestions
+        .suggest(&server.borrow(), ClientId(1), Id(1), {
+            let mut op = Operation::new();
+            op.delete("h".len())
+                .insert("H".into())
+                .retain("ello world".len());
+            op
+        })
+        .unwrap();
+    let typo = suggestions
+        .suggest(&server.borrow(), ClientId(1), Id(1), {
+            let mut op = Operation::new();
+            op.retain("hello".len()).insert(",".into()).retain(" world".len());
+            op
+        })
+        .unwrap();
+    assert_eq!(server.borrow().current_state().id, Id(1));
+    assert_eq!(suggestions.iter().count(), 2);
+
+    // the author keeps editing
+    let connection = mock_connection::MockConnection::new(server.clone());
+    let mut client = block_on(Client::with_connection(&connection)).unwrap();
+    client.push_operation({
+        let mut op = Operation::new();
+        op.insert("oh, ".into()).retain("hello world".len());
+        op
+    });
+    {
+        let patch = block_on(client.send_to_server().unwrap()).unwrap();
+        client.apply_response(patch).unwrap();
+    }
+
+    // suggestions follow the edits
+    assert_eq!(
+        suggestions.preview(&server.borrow(), &fix).unwrap(),
+        "oh, Hello world"
+    );
+    suggestions.catch_up(&server.borrow()).unwrap();
+    assert_eq!(suggestions.get(&fix).unwrap().parent, Id(2));
+
+    // another server does not have the states the suggestions are on
+    assert!(suggestions.catch_up(&Server::new()).is_err());
+    assert_eq!(suggestions.get(&fix).unwrap().parent, Id(2));
+    assert_eq!(
+        suggestions.preview(&server.borrow(), &typo).unwrap(),
+        "oh, hello, world"
+    );
+
+    // accepting a suggestion makes a new state
+    suggestions.accept(&mut server.borrow_mut(), &fix).unwrap();
+    assert_eq!(server.borrow().current_state().content, "oh, Hello world");
+    assert!(suggestions.get(&fix).is_none());
+    assert!(suggestions.accept(&mut server.borrow_mut(), &fix).is_err());
+
+    // a suggestion refused by the server is kept
+    server
+        .borrow_mut()
+        .add_validator(|state: &State<Operation>, op: &Operation, _: &Metadata| {
+            if op.apply(&state.content).contains(',') {
+                Err(Rejection::new("forbidden", ", is not allowed"))
+            } else {
+                Ok(())
+            }
+        });
+    assert!(suggestions.accept(&mut server.borrow_mut(), &typo).is_err());
+    assert_eq!(server.borrow().current_state().content, "oh, Hello world");
+    assert!(suggestions.get(&typo).is_some());
+
+    // rejected suggestions are dropped
+    let rejected = suggestions.reject(&typo).unwrap();
+    assert_eq!(rejected.author, ClientId(1));
+    assert_eq!(suggestions.iter().count(), 0);
+
+    let patch = block_on(client.send_get_patch()).unwrap();
+    client.apply_patch(patch).unwrap();
+    assert_eq!(client.current_content().unwrap(), "oh, Hello world");
+}
@@ -0,0 +1,115 @@
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::tcp_connection::{TcpConnection, TcpConnectionError, TcpServer};
+use ot::server::*;
+use ot::client::*;
+use ot::client::Connection;
+
+use std::io::Cursor;
+use std::thread;
+
+extern crate futures;
+use futures::executor::block_on;
+
+#[test]
+fn test_frame() {
+    use ot::cs::tcp_connection::{read_frame, write_frame};
+
+    let mut buffer = vec![];
+    write_frame(&mut buffer, &"こんにちは".to_string()).unwrap();
+    write_frame(&mut buffer, &vec![1, 2, 3]).unwrap();
+    assert_eq!(&buffer[..4], &[0, 0, 0, "\"こんにちは\"".len() as u8]);
+
+    let mut reader = Cursor::new(buffer);
+    let s: String = read_frame(&mut reader).unwrap();
+    let v: Vec<usize> = read_frame(&mut reader).unwrap();
+    assert_eq!(s, "こんにちは");
+    assert_eq!(v, vec![1, 2, 3]);
+    assert!(read_frame::<_, String>(&mut reader).is_err());
+
+    // the length is checked before reading the message
+    let mut reader = Cursor::new(vec![0xff, 0xff, 0xff, 0xff]);
+    match read_frame::<_, String>(&mut reader) {
+        Err(ref e) if e.kind() == std::io::ErrorKind::InvalidData => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_charwise_tcp() {
+    let listener = TcpServer::<Operation>::bind("127.0.0.1:0", Server::new()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = listener.server();
+
+    let acceptor = thread::spawn(move || {
+        let handle1 = listener.accept().unwrap();
+        let handle2 = listener.accept().unwrap();
+        (handle1, handle2)
+    });
+
+    let connection1 = TcpConnection::connect(addr).unwrap();
+    let connection2 = TcpConnection::connect(addr).unwrap();
+    assert!(connection1.client_id() != connection2.client_id());
+
+    let mut client1 = block_on(Client::with_connection(&connection1)).unwrap();
+    let mut client2 = block_on(Client::with_connection(&connection2)).unwrap();
+    // the state is pushed on connection
+    assert_eq!(connection1.pushed_state().unwrap().id, Id(0));
+
+    client1.push_operation({
+        let mut op = Operation::new();
+        op.insert("こんにちは".into());
+        op
+    });
+    client2.push_operation({
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    });
+
+    // both requests are in flight at the same time
+    let response1 = client1.send_to_server().unwrap();
+    let response2 = client2.send_to_server().unwrap();
+    client2.apply_response(block_on(response2).unwrap()).unwrap();
+    client1.apply_response(block_on(response1).unwrap()).unwrap();
+
+    // the server may have received either operation first
+    {
+        let patch = block_on(client1.send_get_patch()).unwrap();
+        client1.apply_patch(patch).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
+    }
+
+    // the new states are pushed to every client
+    for _ in 0..100 {
+        let pushed = connection1.pushed_state().unwrap().id;
+        if pushed == Id(2) && connection2.pushed_state().unwrap().id == Id(2) {
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(connection1.pushed_state().unwrap().id, Id(2));
+    assert_eq!(connection2.pushed_state().unwrap().id, Id(2));
+
+    let content = server.lock().unwrap().current_state().content.clone();
+    assert_eq!(content.len(), "こんにちは世界".len());
+    assert_eq!(client1.current_content().unwrap(), content);
+    assert_eq!(client2.current_content().unwrap(), content);
+
+    // errors of the server are passed to the client
+    match block_on(connection1.get_patch_since(&Id(100))) {
+        Err(TcpConnectionError::Server(_)) => {}
+        result => panic!("unexpected result {:?}", result.map(|patch| patch.id)),
+    }
+
+    drop(client1);
+    drop(client2);
+    drop(connection1);
+    drop(connection2);
+    let (handle1, handle2) = acceptor.join().unwrap();
+    handle1.join().unwrap().unwrap();
+    handle2.join().unwrap().unwrap();
+}
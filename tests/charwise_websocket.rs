@@ -0,0 +1,99 @@
+#![cfg(feature = "websocket")]
+
+extern crate ot;
+
+use ot::charwise::*;
+use ot::cs::*;
+use ot::cs::websocket::WebSocketServer;
+use ot::server::*;
+
+use std::net::TcpStream;
+use std::thread;
+
+extern crate serde_json;
+use serde_json::Value;
+
+extern crate tungstenite;
+use tungstenite::{Message, WebSocket};
+
+extern crate url;
+use url::Url;
+
+fn send(socket: &mut WebSocket<TcpStream>, text: &str) {
+    socket.write_message(Message::Text(text.into())).unwrap();
+}
+
+fn receive(socket: &mut WebSocket<TcpStream>) -> Value {
+    match socket.read_message().unwrap() {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        message => panic!("unexpected message {:?}", message),
+    }
+}
+
+// speaks the JSON protocol in the same way as a browser
+#[test]
+fn test_charwise_websocket() {
+    let listener = WebSocketServer::<Operation>::bind("127.0.0.1:0", Server::new()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = listener.server();
+    let acceptor = thread::spawn(move || listener.accept().unwrap());
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let url = Url::parse(&format!("ws://{}/", addr)).unwrap();
+    let (mut socket, _) = tungstenite::client(url, stream).unwrap();
+
+    let hello = receive(&mut socket);
+    let client_id = hello["Hello"].as_u64().unwrap();
+    let push = receive(&mut socket);
+    assert_eq!(push["Push"]["id"], 0);
+    assert_eq!(push["Push"]["content"], "");
+
+    send(&mut socket, r#""GetLatestState""#);
+    let reply = receive(&mut socket);
+    assert_eq!(reply["Reply"]["Ok"]["State"]["id"], 0);
+
+    // another client modifies the document meanwhile
+    server.lock().unwrap().modify(Id(0), {
+        let mut op = Operation::new();
+        op.insert("世界".into());
+        op
+    }).unwrap();
+
+    send(
+        &mut socket,
+        &format!(
+            r#"{{"SendOperation": {{"client_id": {}, "seq": 1, "parent": 0, "diff": {{
+                "operations": [{{"Insert": "こんにちは"}}],
+                "source_len": 0, "target_len": 15}}}}}}"#,
+            client_id
+        ),
+    );
+    let reply = receive(&mut socket);
+    let patch = &reply["Reply"]["Ok"]["Patch"];
+    assert_eq!(patch["id"], 2);
+    assert_eq!(patch["checksum"], ot::hash_content("こんにちは世界"));
+    assert_eq!(patch["diff"]["source_len"], "こんにちは".len());
+    assert_eq!(patch["diff"]["target_len"], "こんにちは世界".len());
+    assert_eq!(server.lock().unwrap().current_state().content, "こんにちは世界");
+
+    // the new state is pushed to every client
+    let push = receive(&mut socket);
+    assert_eq!(push["Push"]["id"], 2);
+    assert_eq!(push["Push"]["metadata"]["session"], client_id);
+
+    send(&mut socket, r#"{"GetPatchSince": 1}"#);
+    let reply = receive(&mut socket);
+    assert_eq!(reply["Reply"]["Ok"]["Patch"]["id"], 2);
+
+    // errors are replied in order
+    send(&mut socket, r#"{"GetPatchSince": 100}"#);
+    send(&mut socket, r#"{"Unknown": 0}"#);
+    send(&mut socket, r#""GetLatestState""#);
+    assert!(receive(&mut socket)["Reply"]["Err"].is_string());
+    assert!(receive(&mut socket)["Reply"]["Err"].is_string());
+    assert_eq!(receive(&mut socket)["Reply"]["Ok"]["State"]["id"], 2);
+
+    socket.close(None).unwrap();
+    while socket.read_message().is_ok() {}
+    acceptor.join().unwrap().join().unwrap().unwrap();
+}
@@ -153,3 +153,32 @@ fn fuzz_test_transform() {
         assert_eq!(left.apply(&original), right.apply(&original));
     }
 }
+
+#[test]
+fn fuzz_test_invert() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..100 {
+        let original_len = rng.gen_range(32, 100);
+        let max_line_len = 30;
+        let original = random_lines(&mut rng, max_line_len, original_len);
+
+        let op = random_operation(&mut rng, &original);
+        let applied = op.apply(&original);
+        let inverted = op.invert(&original).unwrap();
+
+        assert_eq!(inverted.apply(&applied), original);
+    }
+}
+
+#[test]
+fn test_checksum() {
+    // the number of lines as a little-endian u64 followed by each line as a string
+    assert_eq!(Operation::checksum(&vec![]), Some(0xa8c7f832281a39c5));
+    assert_eq!(
+        Operation::checksum(&vec!["fn main() {".into(), "}".into()]),
+        Some(0xd95f6d30b6e015f1)
+    );
+}
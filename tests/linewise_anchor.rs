@@ -0,0 +1,134 @@
+extern crate ot;
+
+use ot::anchor::*;
+use ot::selection::Bias;
+use ot::selection::linewise::Position;
+use ot::linewise::Operation as BaseOperation;
+use ot::Operation as OperationTrait;
+
+use std::collections::BTreeMap;
+
+mod util;
+use util::{anchor_id, random_bias};
+use util::linewise::random_position;
+
+extern crate rand;
+use rand::Rng;
+
+fn random_target<R: Rng>(rng: &mut R, anchor_num: usize, len: usize) -> Target<BaseOperation> {
+    let base = util::linewise::random_lines(rng, 10, len);
+    let anchors = (0..anchor_num)
+        .map(|i| {
+            let anchor = Anchor::new(random_position(rng, &base), random_bias(rng));
+            (anchor_id(&i.to_string()), anchor)
+        })
+        .collect();
+    Target { base, anchors }
+}
+
+fn random_operation<R: Rng>(
+    rng: &mut R,
+    anchor_num: usize,
+    target: &Target<BaseOperation>,
+) -> Operation<BaseOperation> {
+    let base = util::linewise::random_operation(rng, &target.base);
+    let lines = base.apply(&target.base);
+    let mut op = target.operate(base);
+    for _ in 0..rng.gen_range(0, 3) {
+        let id = anchor_id(&rng.gen_range(0, anchor_num + 2).to_string());
+        if rng.gen_weighted_bool(3) {
+            op.remove(id);
+        } else {
+            let anchor = Anchor::new(random_position(rng, &lines), random_bias(rng));
+            op.put(id, anchor);
+        }
+    }
+    op
+}
+
+#[test]
+fn test_apply() {
+    let target = Target {
+        base: vec!["fn main() {".into(), "    todo!()".into(), "}".into()],
+        anchors: {
+            let mut anchors = BTreeMap::new();
+            anchors.insert(
+                anchor_id("breakpoint"),
+                Anchor::new(Position { row: 1, col: 4 }, Bias::Right),
+            );
+            anchors.insert(
+                anchor_id("bookmark"),
+                Anchor::new(Position { row: 2, col: 0 }, Bias::Left),
+            );
+            anchors
+        },
+    };
+
+    // the line of the breakpoint is deleted, and a line is inserted at the beginning of the
+    // following line, where the breakpoint moves to
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(1).delete(1).insert("    run();".into()).retain(1);
+        op
+    });
+
+    assert_eq!(
+        op.apply(&target),
+        Target {
+            base: vec!["fn main() {".into(), "    run();".into(), "}".into()],
+            anchors: {
+                let mut anchors = BTreeMap::new();
+                anchors.insert(
+                    anchor_id("breakpoint"),
+                    Anchor::new(Position { row: 2, col: 0 }, Bias::Right),
+                );
+                anchors.insert(
+                    anchor_id("bookmark"),
+                    Anchor::new(Position { row: 1, col: 0 }, Bias::Left),
+                );
+                anchors
+            },
+        }
+    );
+}
+
+#[test]
+fn fuzz_test_compose() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..100 {
+        let len = rng.gen_range(0, 20);
+        let anchor_num = rng.gen_range(1, 30);
+        let target = random_target(&mut rng, anchor_num, len);
+
+        let first = random_operation(&mut rng, anchor_num, &target);
+        let applied = first.apply(&target);
+        let second = random_operation(&mut rng, anchor_num, &applied);
+
+        let double_applied = second.apply(&applied);
+        let compose_applied = first.compose(second).apply(&target);
+
+        assert_eq!(double_applied, compose_applied);
+    }
+}
+
+#[test]
+fn fuzz_test_transform() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 20);
+        let anchor_num = rng.gen_range(1, 30);
+        let target = random_target(&mut rng, anchor_num, len);
+
+        let left = random_operation(&mut rng, anchor_num, &target);
+        let right = random_operation(&mut rng, anchor_num, &target);
+
+        let (left_, right_) = left.clone().transform(right.clone());
+
+        let left = left.compose(right_);
+        let right = right.compose(left_);
+
+        assert_eq!(left.apply(&target), right.apply(&target));
+    }
+}
@@ -0,0 +1,70 @@
+extern crate ot;
+
+use ot::linewise::*;
+use ot::cs::*;
+use ot::cs::attribution::*;
+use ot::server::*;
+
+fn author(author: &str) -> Metadata {
+    Metadata {
+        author: Some(author.into()),
+        ..Metadata::default()
+    }
+}
+
+#[test]
+fn test_linewise_attribution() {
+    let mut server = Server::new();
+    let mut attribution = Attribution::new(&server);
+
+    server
+        .modify_with(author("alice"), Id(0), {
+            let mut op = Operation::new();
+            op.insert("fn main() {".into())
+                .insert("    todo!()".into())
+                .insert("}".into());
+            op
+        })
+        .unwrap();
+
+    // a modified line is attributed to the one who modified it
+    server
+        .modify_with(author("bob"), Id(1), {
+            let mut op = Operation::new();
+            op.retain(1)
+                .modify({
+                    let mut op = ot::charwise::Operation::new();
+                    op.retain("    ".len())
+                        .delete("todo!()".len())
+                        .insert("run();".into());
+                    op
+                })
+                .insert("    exit();".into())
+                .retain(1);
+            op
+        })
+        .unwrap();
+
+    attribution.catch_up(&server);
+    assert_eq!(
+        attribution.spans(),
+        &[
+            Span {
+                len: 1,
+                author: Some("alice".into()),
+                id: Id(1),
+            },
+            Span {
+                len: 2,
+                author: Some("bob".into()),
+                id: Id(2),
+            },
+            Span {
+                len: 1,
+                author: Some("alice".into()),
+                id: Id(1),
+            },
+        ][..]
+    );
+    assert_eq!(attribution.span_at(3).unwrap().id, Id(1));
+}
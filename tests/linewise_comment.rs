@@ -0,0 +1,123 @@
+extern crate ot;
+
+use ot::comment::*;
+use ot::selection::linewise::Position;
+use ot::linewise::Operation as BaseOperation;
+use ot::Operation as OperationTrait;
+
+use std::collections::BTreeMap;
+
+mod util;
+use util::thread_id;
+use util::linewise::random_range;
+
+extern crate rand;
+use rand::Rng;
+
+fn random_target<R: Rng>(rng: &mut R, thread_num: usize, len: usize) -> Target<BaseOperation> {
+    let base = util::linewise::random_lines(rng, 10, len);
+    let threads = (0..thread_num)
+        .map(|i| {
+            let (start, end) = random_range(rng, &base);
+            let thread = Thread {
+                range: Range::new(start, end),
+                resolved: rng.gen(),
+                comments: BTreeMap::new(),
+            };
+            (thread_id(&i.to_string()), thread)
+        })
+        .collect();
+    Target { base, threads }
+}
+
+fn random_operation<R: Rng>(
+    rng: &mut R,
+    thread_num: usize,
+    target: &Target<BaseOperation>,
+) -> Operation<BaseOperation> {
+    let base = util::linewise::random_operation(rng, &target.base);
+    let lines = base.apply(&target.base);
+    let mut op = target.operate(base);
+    for _ in 0..rng.gen_range(0, 5) {
+        let id = thread_id(&rng.gen_range(0, thread_num).to_string());
+        if rng.gen_weighted_bool(5) {
+            op.delete_thread(id);
+        } else if rng.gen() {
+            let (start, end) = random_range(rng, &lines);
+            op.open(id, start, end);
+        } else {
+            let comment = Comment {
+                author: "".into(),
+                body: util::charwise::random_string(rng, 5),
+            };
+            op.comment(id, CommentId(rng.gen_range(0, 5).to_string()), comment);
+        }
+    }
+    op
+}
+
+#[test]
+fn test_apply() {
+    let target = {
+        let target = Target {
+            base: vec!["fn main() {".into(), "    todo!()".into(), "}".into()],
+            threads: BTreeMap::new(),
+        };
+        let mut op = Operation::nop(&target);
+        op.open(
+            thread_id("function"),
+            Position { row: 0, col: 0 },
+            Position { row: 2, col: 1 },
+        ).open(
+            thread_id("todo"),
+            Position { row: 1, col: 4 },
+            Position {
+                row: 1,
+                col: "    todo!()".len(),
+            },
+        );
+        op.apply(&target)
+    };
+
+    // replace the body, which is inside of the range of the whole function
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(1).delete(1).insert("    run();".into()).retain(1);
+        op
+    });
+    let target = op.apply(&target);
+
+    assert_eq!(
+        target.threads[&thread_id("function")].range,
+        Range::new(Position { row: 0, col: 0 }, Position { row: 2, col: 1 })
+    );
+    assert_eq!(
+        target.threads[&thread_id("todo")].range,
+        Range {
+            start: Position { row: 1, col: 0 },
+            end: Position { row: 1, col: 0 },
+            orphaned: true,
+        }
+    );
+}
+
+#[test]
+fn fuzz_test_transform() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 20);
+        let thread_num = rng.gen_range(1, 10);
+        let target = random_target(&mut rng, thread_num, len);
+
+        let left = random_operation(&mut rng, thread_num, &target);
+        let right = random_operation(&mut rng, thread_num, &target);
+
+        let (left_, right_) = left.clone().transform(right.clone());
+
+        let left = left.compose(right_);
+        let right = right.compose(left_);
+
+        assert_eq!(left.apply(&target), right.apply(&target));
+    }
+}
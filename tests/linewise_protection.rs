@@ -0,0 +1,216 @@
+extern crate ot;
+
+use ot::protection::*;
+use ot::linewise::Operation as BaseOperation;
+use ot::selection::linewise::Position;
+use ot::Operation as OperationTrait;
+use ot::cs::*;
+use ot::server::*;
+
+mod util;
+use util::linewise::random_range;
+
+extern crate rand;
+use rand::Rng;
+
+fn license() -> Operation<BaseOperation> {
+    let mut op = Operation::with_content({
+        let mut op = BaseOperation::new();
+        op.insert("// Copyright".into())
+            .insert("// MIT License".into())
+            .insert("fn main() {".into())
+            .insert("}".into());
+        op
+    });
+    op.protect(line(0), line(2));
+    op
+}
+
+fn line(row: usize) -> Position {
+    Position { row: row, col: 0 }
+}
+
+fn random_target<R: Rng>(rng: &mut R, range_num: usize, len: usize) -> Target<BaseOperation> {
+    let base = util::linewise::random_lines(rng, 10, len);
+    let ranges = (0..range_num)
+        .map(|_| {
+            let (start, end) = random_range(rng, &base);
+            Range::new(start, end)
+        })
+        .collect();
+    Target { base, ranges }
+}
+
+fn random_operation<R: Rng>(
+    rng: &mut R,
+    target: &Target<BaseOperation>,
+) -> Operation<BaseOperation> {
+    let base = util::linewise::random_operation(rng, &target.base);
+    if rng.gen_weighted_bool(3) {
+        let lines = base.apply(&target.base);
+        let mut op = target.operate(base);
+        if rng.gen() {
+            let (start, end) = random_range(rng, &lines);
+            op.protect(start, end);
+        } else if let Some(range) = op.ranges.clone().and_then(|ranges| ranges.first().cloned()) {
+            op.unprotect(range.start, range.end);
+        }
+        op
+    } else {
+        target.operate(base)
+    }
+}
+
+#[test]
+fn test_check() {
+    let target = license().apply(&Target::default());
+
+    // lines can be inserted before and after the range
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(2).insert("".into()).retain(2);
+                op
+            })
+            .is_ok()
+    );
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(1).insert("//".into()).retain(3);
+                op
+            })
+            .is_err()
+    );
+
+    // lines in the range can not be modified or deleted
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(1)
+                    .modify({
+                        let mut op = ot::charwise::Operation::new();
+                        op.retain("// MIT License".len()).insert(" 2.0".into());
+                        op
+                    })
+                    .retain(2);
+                op
+            })
+            .is_err()
+    );
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(1).delete(2).retain(1);
+                op
+            })
+            .is_err()
+    );
+    assert!(
+        target
+            .check(&{
+                let mut op = BaseOperation::new();
+                op.retain(2)
+                    .modify({
+                        let mut op = ot::charwise::Operation::new();
+                        op.retain("fn main() {".len()).insert(" // entry".into());
+                        op
+                    })
+                    .retain(1);
+                op
+            })
+            .is_ok()
+    );
+
+    // the range follows edits
+    let target = target
+        .operate({
+            let mut op = BaseOperation::new();
+            op.insert("#!".into()).retain(2).insert("".into()).retain(2);
+            op
+        })
+        .apply(&target);
+    assert_eq!(target.ranges, vec![Range::new(line(1), line(3))]);
+}
+
+#[test]
+fn fuzz_test_transform() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 20);
+        let range_num = rng.gen_range(0, 5);
+        let target = random_target(&mut rng, range_num, len);
+
+        let left = random_operation(&mut rng, &target);
+        let right = random_operation(&mut rng, &target);
+
+        let (left_, right_) = left.clone().transform(right.clone());
+
+        let left = left.compose(right_);
+        let right = right.compose(left_);
+
+        assert_eq!(left.apply(&target), right.apply(&target));
+    }
+}
+
+#[test]
+fn test_linewise_protection_server() {
+    let mut server = Server::new();
+    server.modify(Id(0), license()).unwrap();
+    server.add_validator(Guard);
+
+    // the range moves down with the inserted line
+    server
+        .modify(Id(1), {
+            let mut op = BaseOperation::new();
+            op.insert("#!".into()).retain(4);
+            Operation::with_content(op)
+        })
+        .unwrap();
+    assert_eq!(
+        server.current_state().content.ranges,
+        vec![Range::new(line(1), line(3))]
+    );
+    assert!(
+        server
+            .modify(Id(2), {
+                let mut op = BaseOperation::new();
+                op.retain(2).delete(1).retain(2);
+                Operation::with_content(op)
+            })
+            .is_err()
+    );
+
+    // operations on older states are checked after being transformed
+    server
+        .modify(Id(1), {
+            let mut op = BaseOperation::new();
+            op.delete(1).insert("#!".into()).retain(3);
+            Operation::with_content(op)
+        })
+        .unwrap_err();
+    server
+        .modify(Id(1), {
+            let mut op = BaseOperation::new();
+            op.retain(2).delete(2);
+            Operation::with_content(op)
+        })
+        .unwrap();
+    assert_eq!(
+        server.current_state().content.base,
+        vec!["#!".to_string(), "// Copyright".into(), "// MIT License".into()]
+    );
+
+    // the server itself can change the ranges
+    let content = server.current_state().content.clone();
+    assert_eq!(content.ranges, vec![Range::new(line(1), line(2))]);
+    let mut op = content.operate(BaseOperation::nop(&content.base));
+    op.unprotect(line(1), line(2));
+    server.modify(Id(3), op).unwrap();
+    assert!(server.current_state().content.ranges.is_empty());
+}
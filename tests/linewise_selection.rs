@@ -1,5 +1,6 @@
 extern crate ot;
 use ot::selection::linewise::*;
+use ot::selection::Bias;
 use ot::linewise::Operation as BaseOperation;
 use ot::Operation as OperationTrait;
 
@@ -25,11 +26,16 @@ fn test_apply() {
                     row: 1,
                     col: "世".len(),
                 },
+                Bias::Right,
+                Bias::Right,
+            ),
+            Cursor(
+                Position {
+                    row: 1,
+                    col: "世界".len(),
+                },
+                Bias::Right,
             ),
-            Cursor(Position {
-                row: 1,
-                col: "世界".len(),
-            }),
         ]),
     };
     let op = target.operate({
@@ -57,11 +63,16 @@ fn test_apply() {
                         row: 2,
                         col: "社会".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
+                ),
+                Cursor(
+                    Position {
+                        row: 2,
+                        col: "社会".len(),
+                    },
+                    Bias::Right,
                 ),
-                Cursor(Position {
-                    row: 2,
-                    col: "社会".len(),
-                }),
             ]),
         }
     );
@@ -84,11 +95,16 @@ fn test_compose() {
                     row: 1,
                     col: "世".len(),
                 },
+                Bias::Right,
+                Bias::Right,
+            ),
+            Cursor(
+                Position {
+                    row: 1,
+                    col: "世界".len(),
+                },
+                Bias::Right,
             ),
-            Cursor(Position {
-                row: 1,
-                col: "世界".len(),
-            }),
         ]),
     };
     let first = target.operate({
@@ -123,11 +139,16 @@ fn test_compose() {
                         row: 2,
                         col: "社会".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
+                ),
+                Cursor(
+                    Position {
+                        row: 2,
+                        col: "社会".len(),
+                    },
+                    Bias::Right,
                 ),
-                Cursor(Position {
-                    row: 2,
-                    col: "社会".len(),
-                }),
             ]),
         }
     );
@@ -142,11 +163,16 @@ fn test_compose() {
                         row: 2,
                         col: "社会".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
+                ),
+                Cursor(
+                    Position {
+                        row: 2,
+                        col: "社会".len(),
+                    },
+                    Bias::Right,
                 ),
-                Cursor(Position {
-                    row: 2,
-                    col: "社会".len(),
-                }),
             ]),
         }
     );
@@ -169,11 +195,16 @@ fn test_transform() {
                     row: 1,
                     col: "世".len(),
                 },
+                Bias::Right,
+                Bias::Right,
+            ),
+            Cursor(
+                Position {
+                    row: 0,
+                    col: "こんにち".len(),
+                },
+                Bias::Right,
             ),
-            Cursor(Position {
-                row: 0,
-                col: "こんにち".len(),
-            }),
         ]),
     };
     let left = target.operate({
@@ -188,10 +219,13 @@ fn test_transform() {
     });
     let right = Operation::Op(
         to_selection(vec![
-            Cursor(Position {
-                row: 0,
-                col: "こ".len(),
-            }),
+            Cursor(
+                Position {
+                    row: 0,
+                    col: "こ".len(),
+                },
+                Bias::Right,
+            ),
         ]),
         {
             let mut op = BaseOperation::new();
@@ -216,8 +250,10 @@ fn test_transform() {
                         row: 2,
                         col: "社会".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
-                Cursor(Position { row: 0, col: 0 }),
+                Cursor(Position { row: 0, col: 0 }, Bias::Right),
             ]),
         }
     );
@@ -232,8 +268,122 @@ fn test_transform() {
                         row: 2,
                         col: "社会".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
+                ),
+                Cursor(Position { row: 0, col: 0 }, Bias::Right),
+            ]),
+        }
+    );
+}
+
+#[test]
+fn test_bias() {
+    use ot::selection::linewise::Position;
+    use ot::selection::linewise::Selection::*;
+    use ot::selection::Bias::*;
+
+    let target = Target {
+        base: vec!["a".into(), "b".into()],
+        selection: to_selection(vec![
+            Cursor(Position { row: 1, col: 0 }, Left),
+            Cursor(Position { row: 1, col: 0 }, Right),
+            Cursor(Position { row: 1, col: 1 }, Left),
+        ]),
+    };
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(1).insert("x".into()).modify({
+            let mut op = ot::charwise::Operation::new();
+            op.insert("y".into()).retain(1);
+            op
+        });
+        op
+    });
+
+    assert_eq!(
+        op.apply(&target),
+        Target {
+            base: vec!["a".into(), "x".into(), "yb".into()],
+            selection: to_selection(vec![
+                // stays before the inserted line
+                Cursor(Position { row: 1, col: 0 }, Left),
+                Cursor(Position { row: 2, col: 1 }, Right),
+                Cursor(Position { row: 2, col: 2 }, Left),
+            ]),
+        }
+    );
+}
+
+#[test]
+fn test_direction() {
+    use ot::selection::linewise::Position;
+    use ot::selection::linewise::Selection::*;
+    use ot::selection::Bias::*;
+
+    let target = Target {
+        base: vec!["こんにちは".into(), "世界".into()],
+        selection: to_selection(vec![
+            // selected backward from the second line
+            Range(
+                Position {
+                    row: 1,
+                    col: "世".len(),
+                },
+                Position {
+                    row: 0,
+                    col: "こん".len(),
+                },
+                Right,
+                Right,
+            ),
+            Range(
+                Position { row: 1, col: 0 },
+                Position {
+                    row: 1,
+                    col: "世界".len(),
+                },
+                Left,
+                Right,
+            ),
+        ]),
+    };
+    assert!(target.selection[&()][0].is_backward());
+    assert_eq!(
+        target.selection[&()][0].start(),
+        Position {
+            row: 0,
+            col: "こん".len(),
+        }
+    );
+
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.insert("!".into()).retain(1).modify({
+            let mut op = ot::charwise::Operation::new();
+            op.delete("世界".len());
+            op
+        });
+        op
+    });
+
+    assert_eq!(
+        op.apply(&target),
+        Target {
+            base: vec!["!".into(), "こんにちは".into(), "".into()],
+            selection: to_selection(vec![
+                // the direction is kept
+                Range(
+                    Position { row: 2, col: 0 },
+                    Position {
+                        row: 1,
+                        col: "こん".len(),
+                    },
+                    Right,
+                    Right,
                 ),
-                Cursor(Position { row: 0, col: 0 }),
+                // the selected text was deleted, and the head remains as a cursor
+                Cursor(Position { row: 2, col: 0 }, Right),
             ]),
         }
     );
@@ -301,3 +451,162 @@ fn fuzz_test_transform() {
         assert_eq!(left.apply(&target), right.apply(&target));
     }
 }
+
+#[test]
+fn test_delete_lines() {
+    use ot::selection::linewise::Position;
+    use ot::selection::linewise::Selection::*;
+    use ot::selection::Bias::*;
+
+    let target = Target {
+        base: vec!["a".into(), "bc".into(), "d".into(), "e".into()],
+        selection: to_selection(vec![
+            Cursor(Position { row: 1, col: 1 }, Right),
+            Cursor(Position { row: 2, col: 1 }, Left),
+            Range(
+                Position { row: 0, col: 1 },
+                Position { row: 3, col: 1 },
+                Right,
+                Right,
+            ),
+        ]),
+    };
+    // the lines spanned by the cursors are deleted at once
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(1).delete(2).retain(1);
+        op
+    });
+
+    assert_eq!(
+        op.apply(&target),
+        Target {
+            base: vec!["a".into(), "e".into()],
+            selection: to_selection(vec![
+                Cursor(Position { row: 1, col: 0 }, Right),
+                Cursor(Position { row: 1, col: 0 }, Left),
+                Range(
+                    Position { row: 0, col: 1 },
+                    Position { row: 1, col: 1 },
+                    Right,
+                    Right,
+                ),
+            ]),
+        }
+    );
+
+    // nothing follows the deleted lines
+    let op = target.operate({
+        let mut op = BaseOperation::new();
+        op.retain(2).delete(2);
+        op
+    });
+    assert_eq!(
+        op.apply(&target).selection,
+        to_selection(vec![
+            Cursor(Position { row: 1, col: 1 }, Right),
+            Cursor(Position { row: 1, col: 0 }, Left),
+            Range(
+                Position { row: 0, col: 1 },
+                Position { row: 1, col: 0 },
+                Right,
+                Right,
+            ),
+        ])
+    );
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Fate {
+    Retained,
+    Modified,
+    Deleted,
+}
+
+// what happens to each line of the source
+fn fates(op: &BaseOperation) -> Vec<Fate> {
+    use ot::linewise::LineOperation::*;
+    use std::iter::repeat;
+
+    let mut fates = vec![];
+    for op in op.operations.iter() {
+        match *op {
+            Retain(len) => fates.extend(repeat(Fate::Retained).take(len)),
+            Modify(_) => fates.push(Fate::Modified),
+            Delete(len) => fates.extend(repeat(Fate::Deleted).take(len)),
+            Insert(_) => {}
+        }
+    }
+    fates
+}
+
+fn transform_cursor(
+    base: &[String],
+    op: &BaseOperation,
+    position: Position,
+    bias: Bias,
+) -> Position {
+    let target = Target {
+        base: base.to_vec(),
+        selection: to_selection(vec![Selection::Cursor(position, bias)]),
+    };
+    let selection = target.operate(op.clone()).apply(&target).selection;
+    assert_eq!(selection[&()].len(), 1);
+    selection[&()][0].head()
+}
+
+#[test]
+fn fuzz_test_cursor_mapping() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 20);
+        let base = random_lines(&mut rng, 10, len);
+        let op = util::linewise::random_operation(&mut rng, &base);
+        let applied = op.apply(&base);
+        let fates = fates(&op);
+
+        let mut surviving = vec![];
+        for (row, line) in base.iter().enumerate() {
+            let col = rng.gen_range(0, line.len() + 1);
+            let position = Position { row, col };
+            let bias = util::random_bias(&mut rng);
+            let transformed = transform_cursor(&base, &op, position, bias);
+
+            // the cursor stays in the document
+            assert!(transformed.row < applied.len() || transformed.row == 0);
+            assert!(transformed.col <= applied.get(transformed.row).map_or(0, |l| l.len()));
+
+            match fates[row] {
+                // unless it sticks to the lines inserted before
+                Fate::Retained if col > 0 || bias == Bias::Right => {
+                    assert_eq!(applied[transformed.row], base[row]);
+                    assert_eq!(transformed.col, col);
+                    surviving.push((position, bias, transformed));
+                }
+                Fate::Retained | Fate::Modified => surviving.push((position, bias, transformed)),
+                // moves like the beginning of the next surviving line
+                // with Left bias, it may stick to lines inserted between the deleted ones
+                Fate::Deleted => match (row..base.len()).find(|&r| fates[r] != Fate::Deleted) {
+                    Some(next) => {
+                        let next = Position { row: next, col: 0 };
+                        let expected = transform_cursor(&base, &op, next, bias);
+                        match bias {
+                            Bias::Left => assert!(transformed <= expected),
+                            Bias::Right => assert_eq!(transformed, expected),
+                        }
+                    }
+                    None => assert_eq!(transformed.col, 0),
+                },
+            }
+        }
+
+        // cursors on the surviving lines keep their order
+        surviving.sort_by_key(|&(position, bias, _)| (position, bias == Bias::Right));
+        for pair in surviving.windows(2) {
+            assert!(pair[0].2 <= pair[1].2);
+        }
+    }
+}
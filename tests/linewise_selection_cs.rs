@@ -1,6 +1,7 @@
 extern crate ot;
 
 use ot::selection::linewise::*;
+use ot::selection::Bias;
 use ot::cs::*;
 use ot::server::*;
 use ot::client::*;
@@ -38,10 +39,13 @@ fn test_linewise_selection_client_server() {
 
     client1.push_operation(Operation::Op(
         to_selection(vec![
-            Cursor(Position {
-                row: 0,
-                col: "こんに".len(),
-            }),
+            Cursor(
+                Position {
+                    row: 0,
+                    col: "こんに".len(),
+                },
+                Bias::Right,
+            ),
             Range(
                 Position {
                     row: 0,
@@ -51,6 +55,8 @@ fn test_linewise_selection_client_server() {
                     row: 1,
                     col: "世界".len(),
                 },
+                Bias::Right,
+                Bias::Right,
             ),
         ]),
         {
@@ -60,8 +66,8 @@ fn test_linewise_selection_client_server() {
         },
     ));
     {
-        let (id, op) = block_on(client1.send_to_server().unwrap()).unwrap();
-        client1.apply_patch(id, op).unwrap();
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -69,10 +75,13 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["こんにちは".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position {
-                    row: 0,
-                    col: "こんに".len(),
-                }),
+                Cursor(
+                    Position {
+                        row: 0,
+                        col: "こんに".len(),
+                    },
+                    Bias::Right,
+                ),
                 Range(
                     Position {
                         row: 0,
@@ -82,6 +91,8 @@ fn test_linewise_selection_client_server() {
                         row: 1,
                         col: "世界".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
             ]),
         }
@@ -90,10 +101,13 @@ fn test_linewise_selection_client_server() {
 
     client2.push_operation(Operation::Op(
         to_selection(vec![
-            Cursor(Position {
-                row: 0,
-                col: "!".len(),
-            }),
+            Cursor(
+                Position {
+                    row: 0,
+                    col: "!".len(),
+                },
+                Bias::Right,
+            ),
         ]),
         {
             let mut op = BaseOperation::new();
@@ -102,8 +116,8 @@ fn test_linewise_selection_client_server() {
         },
     ));
     {
-        let (id, op) = block_on(client2.send_to_server().unwrap()).unwrap();
-        client2.apply_patch(id, op).unwrap();
+        let patch = block_on(client2.send_to_server().unwrap()).unwrap();
+        client2.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -111,10 +125,13 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["こんにちは".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position {
-                    row: 0,
-                    col: "こんに".len(),
-                }),
+                Cursor(
+                    Position {
+                        row: 0,
+                        col: "こんに".len(),
+                    },
+                    Bias::Right,
+                ),
                 Range(
                     Position {
                         row: 0,
@@ -124,6 +141,8 @@ fn test_linewise_selection_client_server() {
                         row: 1,
                         col: "世界".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
             ]),
         }
@@ -133,10 +152,13 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["!".into(), "こんにちは".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position {
-                    row: 0,
-                    col: "!".len(),
-                }),
+                Cursor(
+                    Position {
+                        row: 0,
+                        col: "!".len(),
+                    },
+                    Bias::Right,
+                ),
             ]),
         }
     );
@@ -150,8 +172,8 @@ fn test_linewise_selection_client_server() {
         }));
     }
     {
-        let (id, op) = block_on(client1.send_to_server().unwrap()).unwrap();
-        client1.apply_patch(id, op).unwrap();
+        let patch = block_on(client1.send_to_server().unwrap()).unwrap();
+        client1.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -159,13 +181,15 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["!".into(), "さようなら".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position { row: 2, col: 0 }),
+                Cursor(Position { row: 2, col: 0 }, Bias::Right),
                 Range(
                     Position { row: 2, col: 0 },
                     Position {
                         row: 2,
                         col: "世界".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
             ]),
         }
@@ -175,17 +199,20 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["!".into(), "こんにちは".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position {
-                    row: 0,
-                    col: "!".len(),
-                }),
+                Cursor(
+                    Position {
+                        row: 0,
+                        col: "!".len(),
+                    },
+                    Bias::Right,
+                ),
             ]),
         }
     );
 
     {
-        let (id, op) = block_on(client2.send_get_patch()).unwrap();
-        client2.apply_patch(id, op).unwrap();
+        let patch = block_on(client2.send_get_patch()).unwrap();
+        client2.apply_patch(patch).unwrap();
     }
 
     assert_eq!(
@@ -193,13 +220,15 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["!".into(), "さようなら".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position { row: 2, col: 0 }),
+                Cursor(Position { row: 2, col: 0 }, Bias::Right),
                 Range(
                     Position { row: 2, col: 0 },
                     Position {
                         row: 2,
                         col: "世界".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
             ]),
         }
@@ -209,13 +238,15 @@ fn test_linewise_selection_client_server() {
         Target {
             base: vec!["!".into(), "さようなら".into(), "世界".into()],
             selection: to_selection(vec![
-                Cursor(Position { row: 2, col: 0 }),
+                Cursor(Position { row: 2, col: 0 }, Bias::Right),
                 Range(
                     Position { row: 2, col: 0 },
                     Position {
                         row: 2,
                         col: "世界".len(),
                     },
+                    Bias::Right,
+                    Bias::Right,
                 ),
             ]),
         }
@@ -5,6 +5,22 @@ pub fn random_string<R: rand::Rng>(rng: &mut R, len: usize) -> String {
     rng.gen_iter::<char>().take(len).collect()
 }
 
+// a random char boundary of content
+pub fn random_position<R: rand::Rng>(rng: &mut R, content: &str) -> usize {
+    let positions: Vec<_> = content
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(Some(content.len()))
+        .collect();
+    *rng.choose(&positions).unwrap()
+}
+
+pub fn random_range<R: rand::Rng>(rng: &mut R, content: &str) -> (usize, usize) {
+    let start = random_position(rng, content);
+    let end = random_position(rng, content);
+    (start.min(end), start.max(end))
+}
+
 pub fn random_operation<R: rand::Rng>(rng: &mut R, original: &str) -> Operation {
     use util::rand::distributions::{Range, Sample};
 
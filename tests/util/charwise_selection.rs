@@ -0,0 +1,84 @@
+use ot::selection::charwise::*;
+use super::rand;
+use super::random_bias;
+use util::rand::distributions::{Range, Sample};
+
+pub use super::charwise::random_string;
+
+use std::collections::HashMap;
+
+fn random_selection<R: rand::Rng>(
+    rng: &mut R,
+    num_selection: usize,
+    len: usize,
+) -> Vec<Selection> {
+    let mut range = Range::new(0, len + 1);
+    (0..rng.gen_range(0, num_selection))
+        .map(|_| {
+            let anchor = range.sample(rng);
+            let head = range.sample(rng);
+            if anchor == head {
+                Selection::Cursor(head, random_bias(rng))
+            } else {
+                Selection::Range(anchor, head, random_bias(rng), random_bias(rng))
+            }
+        })
+        .collect()
+}
+
+// selections of users 0..num_user
+fn random_selections<R: rand::Rng>(
+    rng: &mut R,
+    num_user: usize,
+    num_selection: usize,
+    len: usize,
+) -> HashMap<usize, Vec<Selection>> {
+    (0..rng.gen_range(0, num_user + 1))
+        .map(|_| {
+            let user = rng.gen_range(0, num_user);
+            (user, random_selection(rng, num_selection, len))
+        })
+        .collect()
+}
+
+pub fn random_target<R: rand::Rng>(
+    rng: &mut R,
+    num_user: usize,
+    num_selection: usize,
+    len: usize,
+) -> Target<usize> {
+    let base = random_string(rng, len);
+    let selection = random_selections(rng, num_user, num_selection, len);
+
+    Target { base, selection }
+}
+
+pub fn random_operation<R: rand::Rng>(
+    rng: &mut R,
+    num_user: usize,
+    num_selection: usize,
+    target: &Target<usize>,
+) -> Operation<usize> {
+    use self::Operation::*;
+
+    match rng.gen_range(0, 4) {
+        0 => Nop,
+        1 => target.select(random_selections(
+            rng,
+            num_user,
+            num_selection,
+            target.base.len(),
+        )),
+        _ => {
+            let op = super::charwise::random_operation(rng, &target.base);
+            let selection = random_selections(rng, num_user, num_selection, op.target_len());
+            Op(selection, op)
+        }
+    }
+}
+
+pub fn to_selection(v: Vec<Selection>) -> HashMap<usize, Vec<Selection>> {
+    let mut ret = HashMap::new();
+    ret.insert(0, v);
+    ret
+}
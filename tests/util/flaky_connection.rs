@@ -0,0 +1,65 @@
+use ot::Operation;
+use ot::cs::*;
+use ot::cs::mock_connection::{MockConnection, MockConnectionError};
+use ot::server::Server;
+use ot::client;
+
+use super::futures::Future;
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+
+// a MockConnection whose requests fail without reaching the server while offline
+pub struct FlakyConnection<O: Operation> {
+    inner: MockConnection<O>,
+    online: Rc<Cell<bool>>,
+}
+
+impl<O: Operation> FlakyConnection<O> {
+    pub fn new(server: Rc<RefCell<Server<O>>>, online: Rc<Cell<bool>>) -> Self {
+        FlakyConnection {
+            inner: MockConnection::new(server),
+            online: online,
+        }
+    }
+
+    fn offline<T: 'static>() -> Box<Future<Item = T, Error = MockConnectionError>> {
+        use util::futures::future::err;
+
+        Box::new(err(String::from("offline").into()))
+    }
+}
+
+impl<O: Operation + 'static> client::Connection<O> for FlakyConnection<O> {
+    type Error = MockConnectionError;
+    type Output = Box<Future<Item = Patch<O>, Error = Self::Error>>;
+    type StateFuture = Box<Future<Item = State<O>, Error = Self::Error>>;
+
+    fn client_id(&self) -> ClientId {
+        self.inner.client_id()
+    }
+
+    fn get_latest_state(&self) -> Self::StateFuture {
+        if self.online.get() {
+            self.inner.get_latest_state()
+        } else {
+            Self::offline()
+        }
+    }
+
+    fn get_patch_since(&self, since_id: &Id) -> Self::Output {
+        if self.online.get() {
+            self.inner.get_patch_since(since_id)
+        } else {
+            Self::offline()
+        }
+    }
+
+    fn send_operation(&self, submission: Submission<O>) -> Self::Output {
+        if self.online.get() {
+            self.inner.send_operation(submission)
+        } else {
+            Self::offline()
+        }
+    }
+}
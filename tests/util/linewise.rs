@@ -1,4 +1,5 @@
 use ot::linewise::*;
+use ot::selection::linewise::Position;
 use super::rand;
 
 use super::charwise as charwise_util;
@@ -19,6 +20,24 @@ pub fn random_lines<R: rand::Rng>(
         .collect()
 }
 
+// a random char boundary of lines
+pub fn random_position<R: rand::Rng>(rng: &mut R, lines: &[String]) -> Position {
+    if lines.is_empty() {
+        return Position { row: 0, col: 0 };
+    }
+    let row = rng.gen_range(0, lines.len());
+    Position {
+        row: row,
+        col: charwise_util::random_position(rng, &lines[row]),
+    }
+}
+
+pub fn random_range<R: rand::Rng>(rng: &mut R, lines: &[String]) -> (Position, Position) {
+    let start = random_position(rng, lines);
+    let end = random_position(rng, lines);
+    (start.min(end), start.max(end))
+}
+
 pub fn random_operation<R: rand::Rng>(rng: &mut R, original: &[String]) -> Operation {
     use util::rand::distributions::{Range, Sample};
 
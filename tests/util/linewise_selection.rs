@@ -1,5 +1,7 @@
 use ot::selection::linewise::*;
+use ot::selection::Bias;
 use super::rand;
+use super::random_bias;
 use util::rand::distributions::{Range, Sample};
 
 pub use super::linewise::random_lines;
@@ -12,7 +14,7 @@ fn random_selection<R: rand::Rng>(
     base: &Vec<String>,
 ) -> Vec<Selection> {
     if base.len() == 0 {
-        vec![Selection::Cursor(Position { row: 0, col: 0 })]
+        vec![Selection::Cursor(Position { row: 0, col: 0 }, Bias::Right)]
     } else {
         let mut range = Range::new(0, base.len());
         (0..rng.gen_range(0, num_selection))
@@ -31,12 +33,11 @@ fn random_selection<R: rand::Rng>(
                     col: end_col,
                 };
 
+                // either direction
                 if start == end {
-                    Selection::Cursor(start)
-                } else if start < end {
-                    Selection::Range(start, end)
+                    Selection::Cursor(start, random_bias(rng))
                 } else {
-                    Selection::Range(end, start)
+                    Selection::Range(start, end, random_bias(rng), random_bias(rng))
                 }
             })
             .collect()
@@ -1,6 +1,29 @@
 extern crate ot;
 extern crate rand;
+extern crate futures;
 
 pub mod charwise;
 pub mod linewise;
+pub mod charwise_selection;
 pub mod linewise_selection;
+pub mod flaky_connection;
+
+use ot::anchor::AnchorId;
+use ot::comment::ThreadId;
+use ot::selection::Bias;
+
+pub fn anchor_id(s: &str) -> AnchorId {
+    AnchorId(s.into())
+}
+
+pub fn thread_id(s: &str) -> ThreadId {
+    ThreadId(s.into())
+}
+
+pub fn random_bias<R: rand::Rng>(rng: &mut R) -> Bias {
+    if rng.gen() {
+        Bias::Left
+    } else {
+        Bias::Right
+    }
+}